@@ -0,0 +1,116 @@
+//! Compares the resizing of the scaled versions of a large image. Run by
+//! `cargo bench --bench resize`, the source size can be given as arguments,
+//! e.g. `cargo bench --bench resize -- 6000 4000`.
+
+use {
+    html5_picture::utils::resample::{
+        CASCADE_MIN_RATIO, PreparedImage, ResizeParameter, ResizeStrategy,
+        resize,
+    },
+    image::{DynamicImage, RgbImage, imageops::FilterType},
+    std::{
+        hint::black_box,
+        time::{Duration, Instant},
+    },
+};
+
+/// Amount of scaled versions, as given to html5-picture.
+const SCALED_IMAGES_COUNT: u32 = 4;
+
+/// Amount of runs of every case, the fastest one is reported.
+const RUNS: u32 = 3;
+
+fn main() {
+    let mut args = std::env::args()
+        .skip(1)
+        .filter_map(|arg| arg.parse::<u32>().ok());
+    let width = args.next().unwrap_or(6000);
+    let height = args.next().unwrap_or(4000);
+    let img = create_image(width, height);
+    // the sizes as calculated by ResizedImageDetails, largest first
+    let step = width as f32 / (SCALED_IMAGES_COUNT as f32 + 1.0);
+    let sizes: Vec<(u32, u32)> = (1..=SCALED_IMAGES_COUNT)
+        .rev()
+        .map(|idx| {
+            let w = (idx as f32 * step).ceil() as u32;
+            (w, (w as f64 / width as f64 * height as f64) as u32)
+        })
+        .collect();
+    println!("Resizing {}x{} to {:?}", width, height, sizes);
+
+    let params = ResizeParameter::default();
+    // the resizer used before, keeping the aspect ratio
+    report("DynamicImage::resize, Triangle", &|| {
+        for (w, h) in &sizes {
+            black_box(img.resize(*w, *h, FilterType::Triangle));
+        }
+    });
+    report("DynamicImage::resize, Lanczos3", &|| {
+        for (w, h) in &sizes {
+            black_box(img.resize(*w, *h, FilterType::Lanczos3));
+        }
+    });
+    report("resample::resize per version", &|| {
+        for (w, h) in &sizes {
+            black_box(resize(&img, *w, *h, &params));
+        }
+    });
+    report("PreparedImage, original strategy", &|| {
+        run_prepared(&img, &sizes, ResizeStrategy::Original);
+    });
+    report("PreparedImage, cascade strategy", &|| {
+        run_prepared(&img, &sizes, ResizeStrategy::Cascade);
+    });
+}
+
+/// Resizes the image to all sizes like the ```SingleProcessor```.
+fn run_prepared(
+    img: &DynamicImage,
+    sizes: &[(u32, u32)],
+    strategy: ResizeStrategy,
+) {
+    let params = ResizeParameter {
+        strategy,
+        ..ResizeParameter::default()
+    };
+    let prepared = PreparedImage::new(img, &params);
+    let mut previous: Option<PreparedImage> = None;
+    for (w, h) in sizes {
+        let resized = match &previous {
+            Some(p)
+                if strategy == ResizeStrategy::Cascade
+                    && p.width() >= w * CASCADE_MIN_RATIO =>
+            {
+                p.resize(*w, *h)
+            }
+            _ => prepared.resize(*w, *h),
+        };
+        black_box(resized.to_image());
+        previous = Some(resized);
+    }
+}
+
+/// Runs the given case and prints its fastest run.
+fn report(name: &str, f: &dyn Fn()) {
+    let fastest = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .min()
+        .unwrap_or(Duration::ZERO);
+    println!("{:<40} {:>8} ms", name, fastest.as_millis());
+}
+
+/// Creates a photo like test image with gradients and fine detail.
+fn create_image(width: u32, height: u32) -> DynamicImage {
+    DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+        let noise = (x.wrapping_mul(7919) ^ y.wrapping_mul(104729)) % 64;
+        image::Rgb([
+            (x * 255 / width) as u8,
+            (y * 255 / height) as u8,
+            ((x + y) % 192 + noise) as u8,
+        ])
+    }))
+}
@@ -0,0 +1,30 @@
+use {
+    html5_picture::mdbook::{run_preprocessor, supports_renderer},
+    log::error,
+    std::io::Read,
+};
+
+fn main() {
+    pretty_env_logger::init();
+
+    // mdBook asks whether a renderer is supported by calling
+    // `mdbook-html5-picture supports <renderer>`
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() == 3 && args[1] == "supports" {
+        let code = if supports_renderer(&args[2]) { 0 } else { 1 };
+        std::process::exit(code);
+    }
+
+    let mut input = String::new();
+    if let Err(msg) = std::io::stdin().read_to_string(&mut input) {
+        error!("{}", msg);
+        std::process::exit(1);
+    }
+    match run_preprocessor(&input) {
+        Ok(book) => println!("{}", book),
+        Err(msg) => {
+            error!("{}", msg);
+            std::process::exit(1);
+        }
+    }
+}
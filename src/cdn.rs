@@ -0,0 +1,203 @@
+//! Instead of encoding the scaled images locally, the `<picture>` tags can
+//! point to an image proxy like [imgproxy](https://imgproxy.net) or
+//! [thumbor](https://www.thumbor.org) that resizes the original image on the
+//! fly. Only the dimensions of the images are read.
+//!
+//! The urls are created from a template containing the placeholders
+//! `{width}`, `{height}`, `{source_url}` and `{signature}`. The source url is
+//! the path of the image relative to the input directory, prefixed by the
+//! mountpoint. The signature is the imgproxy signature of the url part
+//! following it, if a key is given, otherwise `insecure`.
+//!
+//! ## Example
+//!
+//! ```
+//! use {html5_picture::cdn::CdnUrl, std::path::PathBuf};
+//!
+//! let cdn = CdnUrl::new(
+//!     "https://img.example.com/{signature}/rs:fit:{width}/plain/{source_url}@webp",
+//!     PathBuf::from("assets"),
+//!     "s3://bucket",
+//! );
+//! assert_eq!(
+//!     cdn.create_url(&PathBuf::from("assets/hero image.png"), 640, 480).unwrap(),
+//!     "https://img.example.com/insecure/rs:fit:640/plain/s3://bucket/hero%20image.png@webp"
+//! );
+//! ```
+
+use {
+    crate::core::Config,
+    base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD},
+    hmac::{Hmac, Mac},
+    sha2::Sha256,
+    std::path::PathBuf,
+};
+
+/// The placeholder that is replaced by the signature of the url.
+const SIGNATURE_PLACEHOLDER: &str = "{signature}";
+
+/// The signature that is used if no key is given.
+const UNSIGNED: &str = "insecure";
+
+/// Creates the urls of the images served by an image proxy.
+#[derive(Clone, Debug)]
+pub struct CdnUrl {
+    template: String,
+    key: Option<Vec<u8>>,
+    salt: Vec<u8>,
+    source_dir: PathBuf,
+    source_base: String,
+}
+
+impl CdnUrl {
+    /// Creates a new instance for unsigned urls. The source urls of the images
+    /// in `source_dir` are prefixed by `source_base`.
+    pub fn new(template: &str, source_dir: PathBuf, source_base: &str) -> Self {
+        Self {
+            template: template.to_string(),
+            key: None,
+            salt: vec![],
+            source_dir,
+            source_base: source_base.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Creates a new instance from the cdn options of the config. Returns None
+    /// if no url template is given.
+    pub fn from(config: &Config) -> Result<Option<Self>, String> {
+        let template = match &config.cdn_url {
+            Some(t) => t,
+            None => return Ok(None),
+        };
+        let source_base = match &config.mountpoint {
+            Some(m) => match m.to_str() {
+                Some(m) => m,
+                None => {
+                    return Err("utf-8 check failed for mountpoint".to_string());
+                }
+            },
+            None => "",
+        };
+        let cdn = Self::new(template, config.input_dir.clone(), source_base);
+        match &config.cdn_key {
+            Some(key) => {
+                let salt = config.cdn_salt.as_deref().unwrap_or_default();
+                cdn.with_signature(key, salt).map(Some)
+            }
+            None => Ok(Some(cdn)),
+        }
+    }
+
+    /// Signs the urls by the given hex encoded key and salt, as expected by
+    /// imgproxy.
+    /// ## Example
+    ///
+    /// ```
+    /// use {html5_picture::cdn::CdnUrl, std::path::PathBuf};
+    ///
+    /// let cdn = CdnUrl::new(
+    ///     "/{signature}/rs:fit:{width}:{height}/plain/{source_url}",
+    ///     PathBuf::from("images"),
+    ///     "http://img.example.com/pretty",
+    /// )
+    /// // "secret" and "hello"
+    /// .with_signature("736563726574", "68656C6C6F")
+    /// .unwrap();
+    /// assert_eq!(
+    ///     cdn.create_url(&PathBuf::from("images/image.jpg"), 300, 300).unwrap(),
+    ///     "/vZ6TInXHD_gdx-w5FK0bcxhSYxKd-2GkBizoDIGgzSU/rs:fit:300:300/plain/http://img.example.com/pretty/image.jpg"
+    /// );
+    /// ```
+    pub fn with_signature(
+        mut self,
+        key: &str,
+        salt: &str,
+    ) -> Result<Self, String> {
+        self.key = match hex::decode(key) {
+            Ok(k) => Some(k),
+            Err(msg) => return Err(format!("Invalid cdn key: {}", msg)),
+        };
+        self.salt = match hex::decode(salt) {
+            Ok(s) => s,
+            Err(msg) => return Err(format!("Invalid cdn salt: {}", msg)),
+        };
+        Ok(self)
+    }
+
+    /// Creates the url of the given image, resized to the given dimensions.
+    pub fn create_url(
+        &self,
+        image_file_name: &PathBuf,
+        width: u32,
+        height: u32,
+    ) -> Result<String, String> {
+        let url = self
+            .template
+            .replace("{width}", &width.to_string())
+            .replace("{height}", &height.to_string())
+            .replace("{source_url}", &self.get_source_url(image_file_name)?);
+        let (prefix, path) = match url.split_once(SIGNATURE_PLACEHOLDER) {
+            Some(v) => v,
+            None => return Ok(url),
+        };
+        Ok(format!("{}{}{}", prefix, self.sign(path)?, path))
+    }
+
+    /// Returns the url the image proxy loads the original image from.
+    pub(crate) fn get_source_url(
+        &self,
+        image_file_name: &PathBuf,
+    ) -> Result<String, String> {
+        let relative =
+            crate::path::remove_base_dir(&self.source_dir, image_file_name)?;
+        let relative = match relative.to_str() {
+            Some(r) => r.replace('\\', "/"),
+            None => {
+                return Err(format!(
+                    "utf-8 check failed for {}",
+                    image_file_name.display()
+                ));
+            }
+        };
+        let relative = percent_encode(&relative);
+        match self.source_base.is_empty() {
+            true => Ok(relative),
+            false => Ok(format!("{}/{}", self.source_base, relative)),
+        }
+    }
+
+    /// Calculates the signature of the given url path.
+    fn sign(&self, path: &str) -> Result<String, String> {
+        let key = match &self.key {
+            Some(k) => k,
+            None => return Ok(UNSIGNED.to_string()),
+        };
+        let mut mac = match Hmac::<Sha256>::new_from_slice(key) {
+            Ok(m) => m,
+            Err(msg) => return Err(msg.to_string()),
+        };
+        mac.update(&self.salt);
+        mac.update(path.as_bytes());
+        Ok(URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes()))
+    }
+}
+
+/// Encodes all characters of the given path that are not allowed in an url
+/// path segment, keeping the slashes.
+fn percent_encode(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'-'
+            | b'_'
+            | b'.'
+            | b'~'
+            | b'/' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
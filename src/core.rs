@@ -1,6 +1,19 @@
 use {
     crate::{
-        html5::Picture, path, utils, webp::WebpParameter,
+        html5::{Picture, PictureRegister, TagFormat},
+        path,
+        rewrite::{PictureResolver, rewrite_html, rewrite_markdown},
+        server_config::WebServer,
+        utils,
+        utils::{
+            ImageInfo, ImageMetadata, PlaceholderType,
+            color::{ColorParameter, ColorSpace},
+            derivative::Derivative,
+            metadata::MetadataPolicy,
+            resample::{ResizeFilter, ResizeParameter, ResizeStrategy},
+            svg::SvgMode,
+        },
+        webp::WebpParameter,
         webp::processor::BatchParameter,
         webp::processor::Parameter as ProcessorParameter,
     },
@@ -8,10 +21,15 @@ use {
     fs_extra::dir::{
         CopyOptions, TransitProcess, copy_with_progress, move_dir_with_progress,
     },
-    indicatif::MultiProgress,
-    log::error,
+    indicatif::{MultiProgress, ProgressBar},
+    log::{error, warn},
     queue::Queue,
-    std::{path::PathBuf, sync::Arc},
+    std::{
+        cell::OnceCell,
+        collections::HashMap,
+        path::{Path, PathBuf},
+        sync::Arc,
+    },
 };
 
 #[cfg(debug_assertions)]
@@ -19,7 +37,8 @@ use log::debug;
 
 type Step = fn(&mut State);
 
-/// Converts the images (currently png only) of the input folder to webp format.
+/// Converts the images (currently png, gif and svg) of the input folder to
+/// webp format.
 /// It also has the ability to create multiple versions of the input images
 /// having different sizes. See -s for further details.
 /// Additionally it automatically generates HTML5 <picture> tag files for you
@@ -69,6 +88,124 @@ pub struct Config {
     /// If set, the processing is done single threaded.
     #[clap(short)]
     pub single_threaded: bool,
+    /// Generates a low quality image placeholder that is shown while the
+    /// image is loading. Either a tiny blurred webp embedded as data uri into
+    /// the style attribute or a BlurHash in the data-blurhash attribute.
+    #[clap(long, arg_enum)]
+    pub placeholder: Option<PlaceholderType>,
+    /// Extracts a color palette with the given amount of colors from each
+    /// image. The dominant color and the palette are added as
+    /// data-dominant-color and data-palette attributes to the picture tags.
+    #[clap(long)]
+    pub palette: Option<u8>,
+    /// If set, the dominant color is used as background-color of the images
+    /// in the picture tags.
+    #[clap(long)]
+    pub palette_background: bool,
+    /// Defines a named set of images having a fixed aspect ratio, for example
+    /// "thumb: 1:1 @ 200,400" or "card: 16:9 @ 640,1280". Each derivative is
+    /// cropped, resized and saved as <name>-<derivative>-w<width>.webp and
+    /// gets its own picture tag file. Can be given multiple times.
+    #[clap(long = "derivative")]
+    pub derivatives: Vec<Derivative>,
+    /// Enables the pixel density mode for images that are displayed with the
+    /// given fixed CSS width in px, like avatars or logos. Instead of scaled
+    /// images, 1x, 2x and 3x versions are created, capped at the source
+    /// size. The scaled_images_count is ignored in this mode.
+    #[clap(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub density_width: Option<u32>,
+    /// The filter used to resize the images.
+    #[clap(long, arg_enum, default_value = "lanczos3")]
+    pub resize_filter: ResizeFilter,
+    /// Resizes the images in sRGB gamma space instead of linear light. This
+    /// is faster, but darkens fine detail.
+    #[clap(long)]
+    pub gamma_space_resize: bool,
+    /// Sharpens the resized images by an unsharp mask with the given amount,
+    /// e.g. 0.5, to counter the softening of downscaling. The mask is applied
+    /// in sRGB gamma space after resizing.
+    #[clap(long)]
+    pub sharpen: Option<f32>,
+    /// Determines the source the scaled images are resized from. The cascade
+    /// strategy derives each one from the next larger one if that is at
+    /// least twice as large, which is faster at the same quality.
+    #[clap(long, arg_enum, default_value = "cascade")]
+    pub resize_strategy: ResizeStrategy,
+    /// The color space of the converted images. Images with an embedded ICC
+    /// profile, e.g. Adobe RGB, are converted to it, images without one are
+    /// assumed to be sRGB. Display P3 images carry their profile.
+    #[clap(long, arg_enum, default_value = "srgb")]
+    pub color_space: ColorSpace,
+    /// Embeds the ICC profile of the color space into the webp images, even
+    /// if it is sRGB.
+    #[clap(long)]
+    pub embed_icc_profile: bool,
+    /// The EXIF and XMP metadata kept in the webp images. GPS coordinates
+    /// are never kept. The images are always rotated as given by their EXIF
+    /// orientation.
+    #[clap(long = "metadata", arg_enum, default_value = "strip")]
+    pub metadata_policy: MetadataPolicy,
+    /// Determines how SVG images are processed. They are either rasterized
+    /// at the width of every breakpoint and converted to webp, or copied as
+    /// they are and referenced by a plain <img> tag.
+    #[clap(long, arg_enum, default_value = "rasterize")]
+    pub svg_mode: SvgMode,
+    /// The width in px SVG images are rasterized at if they do not have an
+    /// intrinsic size, i.e. no absolute width and height attributes. The
+    /// height is given by the aspect ratio of their viewBox.
+    #[clap(long, default_value = "1920")]
+    pub svg_width: u32,
+    /// The syntax of the files written by -p. Besides plain HTML, the
+    /// pictures can be written as React (jsx, tsx), Vue or Svelte components
+    /// that pass their properties to the <img> tag.
+    #[clap(long, arg_enum, default_value = "html")]
+    pub tag_format: TagFormat,
+    /// Rewrites all HTML files in the given folder in place. Every <img> tag
+    /// referencing a processed image is replaced by its <picture> tag, keeping
+    /// all attributes. The src is resolved against the mountpoint and the
+    /// input directory. Requires -i.
+    #[clap(long)]
+    pub rewrite_html: Option<PathBuf>,
+    /// Writes a manifest of all pictures to the given JSON file. A webserver
+    /// can load it by PictureRegister::from_manifest without reading any
+    /// image. Requires -i.
+    #[clap(long)]
+    pub manifest: Option<PathBuf>,
+    /// Rewrites all Markdown files in the given folder in place. Every image
+    /// reference to a processed image is replaced by its <picture> tag, the
+    /// alt text becomes the alt attribute. Requires -i.
+    #[clap(long)]
+    pub rewrite_markdown: Option<PathBuf>,
+    /// Skips the conversion and only rewrites the documents given by
+    /// --rewrite-html and --rewrite-markdown, using the images installed by a
+    /// previous run. Requires -i.
+    #[clap(long)]
+    pub rewrite_only: bool,
+    /// Writes a configuration snippet for the given web server that serves
+    /// the webp version of an image if the client accepts it. The snippet
+    /// uses the install directory and the mountpoint. Requires -i.
+    #[clap(long, arg_enum)]
+    pub server_config: Option<WebServer>,
+    /// The file the server configuration is written to. Defaults to
+    /// html5-picture.<server>.conf or html5-picture.Caddyfile.
+    #[clap(long)]
+    pub server_config_file: Option<PathBuf>,
+    /// Points the picture tags to an image proxy that resizes the images on
+    /// the fly instead of encoding them locally. The url template may contain
+    /// {width}, {height}, {source_url} and {signature}, for example
+    /// "/{signature}/rs:fit:{width}/plain/{source_url}@webp". The source url
+    /// is the path relative to the input directory, prefixed by -m. Art
+    /// direction and derivatives are skipped with a warning, as the urls
+    /// cannot describe their crop.
+    #[clap(long)]
+    pub cdn_url: Option<String>,
+    /// Hex encoded key used to sign the cdn urls like imgproxy does. Without
+    /// a key, {signature} is replaced by "insecure".
+    #[clap(long)]
+    pub cdn_key: Option<String>,
+    /// Hex encoded salt used to sign the cdn urls.
+    #[clap(long)]
+    pub cdn_salt: Option<String>,
 }
 
 /// Contains the application state and config.
@@ -103,12 +240,12 @@ impl State {
     }
 }
 
-/// Collects all png files in the given input folder.
+/// Collects all png, gif and svg files in the given input folder.
 pub fn collect_file_names(state: &mut State) {
     let pb = utils::create_spinner();
     pb.set_prefix(state.get_prefix());
     pb.set_message("Collecting files to convert...");
-    state.file_names_to_convert = crate::collect_png_file_names(
+    state.file_names_to_convert = crate::collect_image_file_names(
         &state.config.input_dir,
         Some(pb.clone()),
     );
@@ -118,6 +255,47 @@ pub fn collect_file_names(state: &mut State) {
     ));
 }
 
+/// Returns an error if two of the given images would be converted to the same
+/// files, like `logo.png` and `logo.svg` to `logo.webp`, or `photo.png` with
+/// the derivative `thumb` and `photo-thumb.png`.
+/// ## Example
+///
+/// ```
+/// use {html5_picture::core::check_output_file_names, std::path::PathBuf};
+///
+/// let file_names = [PathBuf::from("a/logo.png"), PathBuf::from("b/logo.svg")];
+/// assert!(check_output_file_names(&file_names, &[]).is_ok());
+/// let file_names = [PathBuf::from("a/logo.png"), PathBuf::from("a/logo.svg")];
+/// assert!(check_output_file_names(&file_names, &[]).is_err());
+///
+/// let file_names =
+///     [PathBuf::from("photo.png"), PathBuf::from("photo-thumb.png")];
+/// let derivatives = ["thumb: 1:1 @ 200".parse().unwrap()];
+/// assert!(check_output_file_names(&file_names, &derivatives).is_err());
+/// ```
+pub fn check_output_file_names(
+    file_names: &[PathBuf],
+    derivatives: &[Derivative],
+) -> Result<(), String> {
+    let mut stems = HashMap::new();
+    for file_name in file_names {
+        let mut output_stems = vec![file_name.with_extension("")];
+        for derivative in derivatives {
+            output_stems.push(derivative.get_output_stem(file_name)?);
+        }
+        for stem in output_stems {
+            if let Some(other) = stems.insert(stem, file_name) {
+                return Err(format!(
+                    "{} and {} would be converted to the same files!",
+                    other.display(),
+                    file_name.display()
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Recreates the folder structure of the input directory in the output directory.
 pub fn create_all_output_directories(state: &mut State) {
     let pb = utils::create_spinner();
@@ -167,9 +345,41 @@ pub fn copy_originals_to_output(state: &mut State) {
     ) {
         error!("{}", msg.to_string());
     }
+    pb.set_message("Filtering metadata of original images...");
+    for file_name in &state.file_names_to_convert {
+        if !crate::is_png(file_name) {
+            continue;
+        }
+        if let Err(msg) = filter_original_metadata(&state.config, file_name) {
+            error!("{}", msg);
+        }
+    }
     pb.finish_with_message("Successfully copied original images!");
 }
 
+/// Filters the metadata of the copy of the given original PNG image in the
+/// working directory as given by the metadata policy. The originals are
+/// installed as fallback, so they must not reveal GPS coordinates either.
+fn filter_original_metadata(
+    config: &Config,
+    file_name: &PathBuf,
+) -> Result<(), String> {
+    let output_file_name =
+        path::create_output_file_name(&config.input_dir, file_name)?;
+    let png = match std::fs::read(&output_file_name) {
+        Ok(d) => d,
+        Err(msg) => return Err(msg.to_string()),
+    };
+    let filtered = config.metadata_policy.filter_png(&png)?;
+    if filtered == png {
+        return Ok(());
+    }
+    match std::fs::write(&output_file_name, filtered) {
+        Ok(()) => Ok(()),
+        Err(msg) => Err(msg.to_string()),
+    }
+}
+
 /// Resizes and converts all input images.
 pub fn process_images(state: &mut State) {
     let webp_params = WebpParameter::new(state.config.quality_webp);
@@ -177,9 +387,33 @@ pub fn process_images(state: &mut State) {
         webp_parameter: webp_params,
         input: state.config.input_dir.clone(),
         output_dir: PathBuf::new(),
+        metadata_dir: PathBuf::new(),
         scaled_images_count: state.config.scaled_images_count,
         single_threaded: state.config.single_threaded,
+        placeholder: state.config.placeholder,
+        palette_size: state.config.palette,
+        derivatives: state.config.derivatives.clone(),
+        density_width: state.config.density_width,
+        resize_parameter: ResizeParameter {
+            filter: state.config.resize_filter,
+            linear_light: !state.config.gamma_space_resize,
+            sharpen: state.config.sharpen,
+            strategy: state.config.resize_strategy,
+        },
+        color_parameter: ColorParameter {
+            color_space: state.config.color_space,
+            embed_icc_profile: state.config.embed_icc_profile,
+        },
+        metadata_policy: state.config.metadata_policy,
+        svg_width: state.config.svg_width,
     };
+    // SVGs that are passed through have already been copied
+    let file_names: Vec<PathBuf> = state
+        .file_names_to_convert
+        .iter()
+        .filter(|f| !is_passed_through(&state.config, f))
+        .cloned()
+        .collect();
     let batch_params = BatchParameter {
         single_params: params,
     };
@@ -191,10 +425,16 @@ pub fn process_images(state: &mut State) {
     let pb = utils::create_spinner();
     pb.set_prefix(state.get_prefix());
     pb.set_message("Converting files...");
-    batch_processor.run(&state.file_names_to_convert);
+    batch_processor.run(&file_names);
     pb.finish_with_message("Finished :-)");
 }
 
+/// Returns true if the given image is copied as it is instead of being
+/// converted, which is the case for SVGs in pass-through mode.
+pub fn is_passed_through(config: &Config, image_file_name: &Path) -> bool {
+    config.svg_mode == SvgMode::PassThrough && crate::is_svg(image_file_name)
+}
+
 /// Installs all images that have been converted to the given install folder.
 pub fn install_images_into(state: &mut State) {
     let pb = utils::create_progressbar(0);
@@ -308,115 +548,370 @@ pub fn save_html_picture_tags(state: &mut State) {
         return;
     }
 
+    let derivatives = get_supported_derivatives(&state.config);
     for file_name in &state.file_names_to_convert {
-        use std::io::prelude::*;
+        // read once when the first tag is created, all tags may exist
+        let info = OnceCell::new();
         let mut output_name = file_name.clone();
-        output_name.set_extension("html");
-        let output_tag_file_name =
-            match crate::path::create_output_file_name_with_output_dir(
-                &state.config.picture_tags_output_folder.as_ref().unwrap(),
-                &state.config.input_dir,
-                &output_name,
-            ) {
-                Ok(name) => name,
+        output_name.set_extension(state.config.tag_format.get_extension());
+        if let Err(msg) = save_html_picture_tag(
+            &state.config,
+            file_name,
+            &output_name,
+            || {
+                Picture::from_config(
+                    file_name,
+                    get_image_info(&info, file_name, &state.config)?,
+                    &state.config,
+                )
+            },
+        ) {
+            pb.abandon_with_message(msg);
+            return;
+        }
+
+        for derivative in derivatives {
+            let output_name = match derivative.get_tag_file_name(file_name) {
+                Ok(name) => {
+                    name.with_extension(state.config.tag_format.get_extension())
+                }
                 Err(msg) => {
-                    pb.abandon_with_message(format!("{}", msg.to_string()));
+                    pb.abandon_with_message(msg);
                     return;
                 }
             };
+            if let Err(msg) = save_html_picture_tag(
+                &state.config,
+                file_name,
+                &output_name,
+                || {
+                    Picture::from_derivative_config(
+                        file_name,
+                        get_image_info(&info, file_name, &state.config)?,
+                        derivative,
+                        &state.config,
+                    )
+                },
+            ) {
+                pb.abandon_with_message(msg);
+                return;
+            }
+        }
+        pb.inc(1);
+    }
+    pb.finish_with_message(format!(
+        "Successfully wrote HTML picture tag files to: {}",
+        &state
+            .config
+            .picture_tags_output_folder
+            .as_ref()
+            .unwrap()
+            .display()
+    ));
+}
+
+/// Returns the derivatives that can be created with the given config. The
+/// urls of an image proxy cannot describe the crop of a derivative, so they
+/// are skipped with a warning.
+pub fn get_supported_derivatives(config: &Config) -> &[Derivative] {
+    if config.cdn_url.is_some() && !config.derivatives.is_empty() {
+        warn!("Derivatives are not supported by --cdn-url and are skipped!");
+        return &[];
+    }
+    &config.derivatives
+}
 
+/// Returns the information about the given image, reading it on first use.
+fn get_image_info<'a>(
+    info: &'a OnceCell<ImageInfo>,
+    file_name: &Path,
+    config: &Config,
+) -> Result<&'a ImageInfo, String> {
+    if let Some(info) = info.get() {
+        return Ok(info);
+    }
+    let image_info = ImageInfo::with_svg_width(file_name, config.svg_width)?;
+    Ok(info.get_or_init(|| image_info))
+}
+
+/// Writes the `<picture>` tag of the given image to the tag file `output_name`
+/// located in the picture tags output folder. The picture is only created if
+/// the file does not exist or force_overwrite is set.
+fn save_html_picture_tag(
+    config: &Config,
+    file_name: &PathBuf,
+    output_name: &PathBuf,
+    create_picture: impl FnOnce() -> Result<Picture, String>,
+) -> Result<(), String> {
+    use std::io::prelude::*;
+    let output_tag_file_name =
+        crate::path::create_output_file_name_with_output_dir(
+            config.picture_tags_output_folder.as_ref().unwrap(),
+            &config.input_dir,
+            output_name,
+        )?;
+
+    #[cfg(debug_assertions)]
+    debug!("{:#?}", output_tag_file_name);
+
+    if std::path::Path::new(&output_tag_file_name).exists()
+        && !config.force_overwrite
+    {
         #[cfg(debug_assertions)]
-        debug!("{:#?}", output_tag_file_name);
+        debug!("Skipping file {:#?}", output_tag_file_name);
+        return Ok(());
+    }
 
-        if std::path::Path::new(&output_tag_file_name).exists()
-            && !state.config.force_overwrite
-        {
-            #[cfg(debug_assertions)]
-            debug!("Skipping file {:#?}", output_tag_file_name);
-            continue;
+    let parent_folder = match output_tag_file_name.parent() {
+        Some(p) => p,
+        None => {
+            return Err(format!(
+                "No parent folder available for {}",
+                output_tag_file_name.display()
+            ));
         }
+    };
+    let is_folder = match std::fs::metadata(parent_folder) {
+        Ok(v) => v.is_dir(),
+        Err(_) => false,
+    };
+    if !is_folder && let Err(msg) = std::fs::create_dir_all(parent_folder) {
+        return Err(format!("Parent folder could not be created: {}", msg));
+    }
+
+    let mut pic = create_picture()?;
+
+    match path::create_metadata_file_name(&config.input_dir, file_name)
+        .and_then(|f| ImageMetadata::from(&f))
+    {
+        Ok(metadata) => pic.apply_metadata(metadata),
+        Err(msg) => error!("{}", msg),
+    };
+    pic.palette_background = config.palette_background;
 
-        let parent_folder = match output_tag_file_name.parent() {
-            Some(p) => p,
+    // the urls of the image proxy already contain the mountpoint
+    if let (Some(mountpoint), None) = (&config.mountpoint, &config.cdn_url) {
+        pic.apply_mountpoint(mountpoint, &config.input_dir)?;
+    }
+
+    let mut html_file = match std::fs::File::create(output_tag_file_name) {
+        Ok(f) => f,
+        Err(msg) => return Err(msg.to_string()),
+    };
+    let component_name = match output_name.file_stem().and_then(|f| f.to_str())
+    {
+        Some(f) => TagFormat::get_component_name(f),
+        None => return Err("utf-8 check failed for tag filename".to_string()),
+    };
+    let tag = pic.to_tag_string(config.tag_format, &component_name, None);
+    if let Err(msg) = html_file.write_all(tag.as_bytes()) {
+        error!("{}", msg.to_string());
+    };
+    Ok(())
+}
+
+/// Writes the manifest of all installed pictures to the file given by the
+/// options.
+pub fn save_picture_manifest(state: &mut State) {
+    let pb = utils::create_spinner();
+    pb.set_prefix(state.get_prefix());
+    pb.set_message("Writing picture manifest...");
+    let file_name = match &state.config.manifest {
+        Some(v) => v,
+        None => {
+            pb.abandon_with_message("Parameter manifest not set!");
+            return;
+        }
+    };
+    let register = match PictureRegister::from(&state.config) {
+        Ok(r) => r,
+        Err(msg) => {
+            pb.abandon_with_message(msg);
+            return;
+        }
+    };
+    if let Err(msg) = register.to_manifest().save(file_name) {
+        pb.abandon_with_message(msg);
+        return;
+    }
+    pb.finish_with_message(format!(
+        "Successfully wrote picture manifest to: {}",
+        file_name.display()
+    ));
+}
+
+/// Writes the web server configuration snippet given by the options.
+pub fn save_server_config(state: &mut State) {
+    let pb = utils::create_spinner();
+    pb.set_prefix(state.get_prefix());
+    pb.set_message("Writing web server configuration...");
+    let server = match state.config.server_config {
+        Some(v) => v,
+        None => {
+            pb.abandon_with_message("Parameter server-config not set!");
+            return;
+        }
+    };
+    let install_dir = match &state.config.install_images_into {
+        Some(v) => v,
+        None => {
+            pb.abandon_with_message("Parameter install_images_into not set!");
+            return;
+        }
+    };
+    // the snippet is included by the server from a different folder
+    let install_dir = match install_dir.canonicalize() {
+        Ok(v) => v,
+        Err(msg) => {
+            pb.abandon_with_message(msg.to_string());
+            return;
+        }
+    };
+    let mountpoint = match &state.config.mountpoint {
+        Some(m) => match m.to_str() {
+            Some(m) => m,
             None => {
-                pb.abandon_with_message(format!(
-                    "No parent folder available for {}",
-                    output_tag_file_name.display()
-                ));
-                return;
-            }
-        };
-        let is_folder = match std::fs::metadata(parent_folder) {
-            Ok(v) => v.is_dir(),
-            Err(_) => false,
-        };
-        if !is_folder {
-            if let Err(msg) = std::fs::create_dir_all(parent_folder) {
-                error!(
-                    "Parent folder could not be created: {}",
-                    msg.to_string()
-                );
+                pb.abandon_with_message("utf-8 check failed for mountpoint");
                 return;
             }
+        },
+        None => "",
+    };
+    // SVGs are served as they are, even if they have been rasterized for the
+    // picture tags, as the vector graphic is sharper than any webp version
+    let file_names: Vec<&PathBuf> = state
+        .file_names_to_convert
+        .iter()
+        .filter(|f| !crate::is_svg(f))
+        .collect();
+    let mut extensions: Vec<String> = file_names
+        .iter()
+        .filter_map(|f| f.extension().and_then(|e| e.to_str()))
+        .map(|e| e.to_string())
+        .collect();
+    extensions.sort();
+    extensions.dedup();
+    if extensions.is_empty() {
+        pb.abandon_with_message("No images found to configure the server for!");
+        return;
+    }
+    let config =
+        server.render(&install_dir, mountpoint, &extensions, file_names.len());
+    let file_name = match &state.config.server_config_file {
+        Some(f) => f.clone(),
+        None => PathBuf::from(server.get_default_file_name()),
+    };
+    if let Err(msg) = std::fs::write(&file_name, config) {
+        pb.abandon_with_message(msg.to_string());
+        return;
+    }
+    pb.finish_with_message(format!(
+        "Successfully wrote web server configuration to: {}",
+        file_name.display()
+    ));
+}
+
+/// Replaces the `<img>` tags of all HTML files in the folder given by the
+/// options by `<picture>` tags.
+pub fn rewrite_html_files(state: &mut State) {
+    let pb = utils::create_spinner();
+    pb.set_prefix(state.get_prefix());
+    pb.set_message("Rewriting HTML files...");
+    let html_dir = match &state.config.rewrite_html {
+        Some(v) => v,
+        None => {
+            pb.abandon_with_message("Parameter rewrite_html not set!");
+            return;
         }
+    };
+    let rewritten_files = rewrite_documents(
+        &state.config,
+        html_dir,
+        &["html", "htm"],
+        &pb,
+        |html, resolve| rewrite_html(html, resolve),
+    );
+    match rewritten_files {
+        Ok(count) => {
+            pb.finish_with_message(format!("Rewrote {} HTML files!", count))
+        }
+        Err(msg) => pb.abandon_with_message(msg),
+    }
+}
 
-        let mut pic =
-            Picture::from(&file_name, state.config.scaled_images_count)
-                .unwrap();
-
-        if let Some(mountpoint) = &state.config.mountpoint {
-            for source in &mut pic.sources {
-                source.srcset =
-                    match crate::path::create_output_file_name_with_output_dir(
-                        &mountpoint,
-                        &state.config.input_dir,
-                        &PathBuf::from(&source.srcset),
-                    ) {
-                        Ok(name) => String::from(name.to_str().unwrap()),
-                        Err(msg) => {
-                            pb.abandon_with_message(format!(
-                                "{}",
-                                msg.to_string()
-                            ));
-                            return;
-                        }
-                    };
-            }
-            pic.fallback_uri =
-                match crate::path::create_output_file_name_with_output_dir(
-                    &mountpoint,
-                    &state.config.input_dir,
-                    &PathBuf::from(&pic.fallback_uri),
-                ) {
-                    Ok(name) => String::from(name.to_str().unwrap()),
-                    Err(msg) => {
-                        pb.abandon_with_message(format!("{}", msg.to_string()));
-                        return;
-                    }
-                };
+/// Replaces the image references of all Markdown files in the folder given
+/// by the options by `<picture>` tags.
+pub fn rewrite_markdown_files(state: &mut State) {
+    let pb = utils::create_spinner();
+    pb.set_prefix(state.get_prefix());
+    pb.set_message("Rewriting Markdown files...");
+    let markdown_dir = match &state.config.rewrite_markdown {
+        Some(v) => v,
+        None => {
+            pb.abandon_with_message("Parameter rewrite_markdown not set!");
+            return;
         }
+    };
+    let rewritten_files = rewrite_documents(
+        &state.config,
+        markdown_dir,
+        &["md", "markdown"],
+        &pb,
+        |markdown, resolve| Ok(rewrite_markdown(markdown, resolve)),
+    );
+    match rewritten_files {
+        Ok(count) => {
+            pb.finish_with_message(format!("Rewrote {} Markdown files!", count))
+        }
+        Err(msg) => pb.abandon_with_message(msg),
+    }
+}
+
+/// Rewrites all documents with the given extensions in the folder in place.
+/// Returns the amount of files that have been changed.
+fn rewrite_documents(
+    config: &Config,
+    dir: &PathBuf,
+    extensions: &[&str],
+    pb: &ProgressBar,
+    rewrite: impl Fn(
+        &str,
+        &dyn Fn(&str) -> Option<Picture>,
+    ) -> Result<String, String>,
+) -> Result<usize, String> {
+    let register = PictureRegister::from(config)?;
+    let resolver = PictureResolver::new(&register, config);
 
-        let mut html_file = match std::fs::File::create(output_tag_file_name) {
-            Ok(f) => f,
+    let mut rewritten_files = 0;
+    for file_name in crate::collect_file_names_with_extensions(dir, extensions)
+    {
+        pb.tick();
+        let content = match std::fs::read_to_string(&file_name) {
+            Ok(c) => c,
             Err(msg) => {
-                error!("{}", msg.to_string());
-                return;
+                error!("{}: {}", file_name.display(), msg);
+                continue;
             }
         };
-        if let Err(msg) =
-            html_file.write_all(pic.to_html_string(None, "").as_bytes())
-        {
-            error!("{}", msg.to_string());
+        let mut document_dir = file_name.clone();
+        document_dir.pop();
+        let rewritten = match rewrite(&content, &|src| {
+            resolver.resolve(src, &document_dir)
+        }) {
+            Ok(v) => v,
+            Err(msg) => {
+                error!("{}: {}", file_name.display(), msg);
+                continue;
+            }
         };
-        pb.inc(1);
+        if rewritten == content {
+            continue;
+        }
+        if let Err(msg) = std::fs::write(&file_name, rewritten) {
+            error!("{}: {}", file_name.display(), msg);
+            continue;
+        }
+        rewritten_files += 1;
     }
-    pb.finish_with_message(format!(
-        "Successfully wrote HTML picture tag files to: {}",
-        &state
-            .config
-            .picture_tags_output_folder
-            .as_ref()
-            .unwrap()
-            .display()
-    ));
+    Ok(rewritten_files)
 }
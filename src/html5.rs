@@ -1,33 +1,166 @@
 use {
-    crate::{core::Config, utils::ResizedImageDetails},
+    crate::{
+        cdn::CdnUrl,
+        core::{Config, get_supported_derivatives, is_passed_through},
+        utils::{
+            ImageInfo, ImageMetadata, Placeholder, ResizedImageDetails,
+            Sidecar, animation, derivative::Derivative,
+        },
+    },
+    log::warn,
     serde::{Deserialize, Serialize},
-    std::{collections::HashMap, path::PathBuf},
+    std::{
+        collections::HashMap,
+        path::{Path, PathBuf},
+        sync::Arc,
+    },
+};
+
+mod manifest;
+mod reloading;
+mod tag_format;
+
+pub use {
+    manifest::PictureManifest,
+    reloading::{RegisterSource, ReloadingPictureRegister},
+    tag_format::TagFormat,
 };
 
 type PathBufPictureRegister = HashMap<PathBuf, Picture>;
+type DerivativePictureRegister = HashMap<PathBuf, HashMap<String, Picture>>;
+
+/// Determines the uris of the resized images of a picture.
+#[derive(Clone, Copy)]
+enum ImageUris<'a> {
+    /// The resized images are located next to the image.
+    Local,
+    /// The images are resized on the fly by an image proxy.
+    Cdn(&'a CdnUrl),
+}
+
+impl ImageUris<'_> {
+    /// Returns the uri of the resized image described by `details`. Cropped
+    /// images are not supported by the image proxy, as its urls cannot
+    /// describe the crop rectangle of the focal point or smart crop.
+    fn get_uri(
+        &self,
+        image_file_name: &PathBuf,
+        details: &ResizedImageDetails,
+    ) -> Result<String, String> {
+        match self {
+            Self::Local => {
+                let mut input_dir = image_file_name.clone();
+                input_dir.pop();
+                match input_dir.join(&details.output_file_name).to_str() {
+                    Some(v) => Ok(v.to_string()),
+                    None => {
+                        Err(String::from("Could not convert output_file_name!"))
+                    }
+                }
+            }
+            Self::Cdn(_) if details.crop.is_some() => Err(format!(
+                "Cropped images are not supported by --cdn-url: {}",
+                image_file_name.display()
+            )),
+            Self::Cdn(cdn) => {
+                cdn.create_url(image_file_name, details.width, details.height)
+            }
+        }
+    }
+
+    /// Returns the uri of the `<img>` tag, which is the original image or
+    /// its full size version served by the image proxy. Animated images are
+    /// replaced by the static poster of their first frame.
+    fn get_fallback_uri(
+        &self,
+        image_file_name: &PathBuf,
+        info: &ImageInfo,
+    ) -> Result<String, String> {
+        match self {
+            Self::Local => {
+                let fallback = match info.animated {
+                    true => image_file_name.with_file_name(
+                        animation::get_poster_file_name(image_file_name)?,
+                    ),
+                    false => image_file_name.clone(),
+                };
+                match fallback.to_str() {
+                    Some(v) => Ok(v.to_string()),
+                    None => {
+                        Err(String::from("Could not convert image file name!"))
+                    }
+                }
+            }
+            Self::Cdn(cdn) => {
+                cdn.create_url(image_file_name, info.width, info.height)
+            }
+        }
+    }
+
+    /// Returns the uri of the unmodified image, which is served next to the
+    /// resized images or by the origin of the image proxy.
+    fn get_original_uri(
+        &self,
+        image_file_name: &PathBuf,
+    ) -> Result<String, String> {
+        match self {
+            Self::Local => match image_file_name.to_str() {
+                Some(v) => Ok(v.to_string()),
+                None => Err(String::from("Could not convert image file name!")),
+            },
+            Self::Cdn(cdn) => cdn.get_source_url(image_file_name),
+        }
+    }
+}
 
 /// Contains information about the MediaWidth property of a ```<picture>``` that
 /// are required for its creation.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub enum MediaWidth {
     Max(String),
     Min(String),
 }
 
 /// Information about the source attributes in a ```<picture>``` tag.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct SourceAttributes {
-    pub media_width: MediaWidth,
+    /// The media condition of the source. Sources without condition are
+    /// used for all viewport widths.
+    pub media_width: Option<MediaWidth>,
     pub srcset: String,
+    /// The pixel density descriptor of the srcset, e.g. 2 for `2x`.
+    #[serde(default)]
+    pub density: Option<u8>,
 }
 
 /// Represents the HTML5 ```<picture>``` tag.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Picture {
     /// Contains the `<source>` tags of the picture.
     pub sources: Vec<SourceAttributes>,
     /// Specifies the fallback uri of the picture.
     pub fallback_uri: String,
+    /// The low quality image placeholder that is shown while loading.
+    #[serde(default)]
+    pub placeholder: Option<Placeholder>,
+    /// The dominant color of the image as CSS hex string.
+    #[serde(default)]
+    pub dominant_color: Option<String>,
+    /// The color palette of the image as CSS hex strings.
+    #[serde(default)]
+    pub palette: Vec<String>,
+    /// True if the dominant color is used as background-color of the
+    /// `<img>` tag.
+    #[serde(default)]
+    pub palette_background: bool,
+    /// True if the image is an animated gif or APNG. Its fallback uri points
+    /// to a static poster of the first frame then.
+    #[serde(default)]
+    pub animated: bool,
+    /// True if the image is used as it is without any converted version,
+    /// like SVGs in pass-through mode. It is rendered as plain `<img>` tag.
+    #[serde(default)]
+    pub pass_through: bool,
 }
 
 impl Picture {
@@ -36,34 +169,97 @@ impl Picture {
     pub fn from(
         image_file_name: &PathBuf,
         scaled_images_count: u8,
+    ) -> Result<Self, String> {
+        Self::from_scaled_images(
+            image_file_name,
+            &ImageInfo::from(image_file_name)?,
+            scaled_images_count,
+            ImageUris::Local,
+        )
+    }
+
+    /// Collects all information about the image required for the creation of
+    /// a ```<picture>``` tag, respecting the mode given by the config. If a
+    /// cdn url is configured, the uris point to the image proxy. The `info`
+    /// has been read from the image before, so it can be shared with its
+    /// derivatives.
+    pub fn from_config(
+        image_file_name: &PathBuf,
+        info: &ImageInfo,
+        config: &Config,
+    ) -> Result<Self, String> {
+        let cdn = CdnUrl::from(config)?;
+        let uris = match &cdn {
+            Some(cdn) => ImageUris::Cdn(cdn),
+            None => ImageUris::Local,
+        };
+        if is_passed_through(config, image_file_name) {
+            return Self::from_original(image_file_name, uris);
+        }
+        match config.density_width {
+            Some(css_width) => {
+                Self::from_densities(image_file_name, info, css_width, uris)
+            }
+            None => Self::from_scaled_images(
+                image_file_name,
+                info,
+                config.scaled_images_count,
+                uris,
+            ),
+        }
+    }
+
+    /// Creates the picture of the scaled images, using the given uris.
+    fn from_scaled_images(
+        image_file_name: &PathBuf,
+        info: &ImageInfo,
+        scaled_images_count: u8,
+        uris: ImageUris,
     ) -> Result<Self, String> {
         if scaled_images_count == 0 {
             return Err("scaled_images_count must be > 0".to_string());
         }
-        let resized_image_details =
-            ResizedImageDetails::from(&image_file_name, scaled_images_count)?;
+        let resized_image_details = ResizedImageDetails::from(
+            image_file_name,
+            info,
+            scaled_images_count,
+        )?;
         let mut sources = vec![];
-        let mut input_dir = image_file_name.clone();
-        input_dir.pop();
+        // art directed crops need to be first, the browser uses the first
+        // matching source
+        let mut art_direction = Sidecar::from(image_file_name)?.art_direction;
+        art_direction.sort_by_key(|a| a.max_width);
+        if let ImageUris::Cdn(_) = uris
+            && !art_direction.is_empty()
+        {
+            warn!(
+                "The art direction of {} is skipped, the urls of --cdn-url cannot describe its crops!",
+                image_file_name.display()
+            );
+            art_direction.clear();
+        }
+        let art_details = ResizedImageDetails::from_art_direction(
+            image_file_name,
+            info,
+            &art_direction,
+        )?;
+        for (art, details) in art_direction.iter().zip(art_details) {
+            sources.push(SourceAttributes {
+                media_width: Some(MediaWidth::Max(art.max_width.to_string())),
+                srcset: uris.get_uri(image_file_name, &details)?,
+                density: None,
+            });
+        }
         for details in &resized_image_details {
-            let out_file_name =
-                match input_dir.join(&details.output_file_name).to_str() {
-                    Some(v) => v.to_string(),
-                    None => {
-                        return Err(String::from(
-                            "Could not convert output_file_name!",
-                        ));
-                    }
-                };
             sources.push(SourceAttributes {
-                media_width: MediaWidth::Max(details.width.to_string()),
-                srcset: out_file_name,
+                media_width: Some(MediaWidth::Max(details.width.to_string())),
+                srcset: uris.get_uri(image_file_name, details)?,
+                density: None,
             });
         }
-        let mut full_scale_image = image_file_name.clone();
-        full_scale_image.set_extension("webp");
-        let full_scale_image = match full_scale_image.to_str() {
-            Some(v) => v.to_string(),
+        let (w, h) = (info.width, info.height);
+        let full_scale_image = match image_file_name.file_name() {
+            Some(f) => PathBuf::from(f).with_extension("webp"),
             None => {
                 return Err(String::from(
                     "Could not convert full_scale_image file name!",
@@ -71,48 +267,450 @@ impl Picture {
             }
         };
         sources.push(SourceAttributes {
-            media_width: MediaWidth::Min(
+            media_width: Some(MediaWidth::Min(
                 // unwrap allowed as long as scaled_images_count > 0
                 (resized_image_details.last().unwrap().width + 1).to_string(),
-            ),
-            srcset: full_scale_image,
+            )),
+            srcset: uris.get_uri(
+                image_file_name,
+                &ResizedImageDetails::new(full_scale_image, w, h),
+            )?,
+            density: None,
         });
 
-        Ok(Self {
+        let mut picture = Self::from_uris(
             sources,
-            fallback_uri: image_file_name.to_str().unwrap().to_string(),
-        })
+            uris.get_fallback_uri(image_file_name, info)?,
+        );
+        picture.animated = info.animated;
+        Ok(picture)
+    }
+
+    /// Collects all information about an image that is displayed with a fixed
+    /// CSS width. The resulting ```<picture>``` tag contains a single srcset
+    /// with pixel density descriptors, for example
+    /// `srcset="a-1x.webp 1x, a-2x.webp 2x"`.
+    pub fn from_pixel_density(
+        image_file_name: &PathBuf,
+        css_width: u32,
+    ) -> Result<Self, String> {
+        Self::from_densities(
+            image_file_name,
+            &ImageInfo::from(image_file_name)?,
+            css_width,
+            ImageUris::Local,
+        )
+    }
+
+    /// Creates the picture of the pixel density versions, using the given
+    /// uris.
+    fn from_densities(
+        image_file_name: &PathBuf,
+        info: &ImageInfo,
+        css_width: u32,
+        uris: ImageUris,
+    ) -> Result<Self, String> {
+        if css_width == 0 {
+            return Err("css_width must be > 0".to_string());
+        }
+        let resized_image_details = ResizedImageDetails::from_pixel_density(
+            image_file_name,
+            info,
+            css_width,
+            ResizedImageDetails::MAX_PIXEL_DENSITY,
+        )?;
+        let mut sources = vec![];
+        for (idx, details) in resized_image_details.iter().enumerate() {
+            sources.push(SourceAttributes {
+                media_width: None,
+                srcset: uris.get_uri(image_file_name, details)?,
+                density: Some(idx as u8 + 1),
+            });
+        }
+        let mut picture = Self::from_uris(
+            sources,
+            uris.get_fallback_uri(image_file_name, info)?,
+        );
+        picture.animated = info.animated;
+        Ok(picture)
+    }
+
+    /// Collects all information about the given derivative of the image
+    /// required for the creation of a ```<picture>``` tag. The biggest image
+    /// of the derivative is used as fallback.
+    pub fn from_derivative(
+        image_file_name: &PathBuf,
+        derivative: &Derivative,
+    ) -> Result<Self, String> {
+        Self::from_derivative_images(
+            image_file_name,
+            &ImageInfo::from(image_file_name)?,
+            derivative,
+            ImageUris::Local,
+        )
+    }
+
+    /// Collects all information about the given derivative of the image,
+    /// pointing to the image proxy if a cdn url is configured.
+    pub fn from_derivative_config(
+        image_file_name: &PathBuf,
+        info: &ImageInfo,
+        derivative: &Derivative,
+        config: &Config,
+    ) -> Result<Self, String> {
+        let cdn = CdnUrl::from(config)?;
+        let uris = match &cdn {
+            Some(cdn) => ImageUris::Cdn(cdn),
+            None => ImageUris::Local,
+        };
+        // vector images fit any aspect ratio by CSS
+        if is_passed_through(config, image_file_name) {
+            return Self::from_original(image_file_name, uris);
+        }
+        Self::from_derivative_images(image_file_name, info, derivative, uris)
+    }
+
+    /// Creates the picture of an image that is used as it is, without any
+    /// source.
+    fn from_original(
+        image_file_name: &PathBuf,
+        uris: ImageUris,
+    ) -> Result<Self, String> {
+        let mut picture =
+            Self::from_uris(vec![], uris.get_original_uri(image_file_name)?);
+        picture.pass_through = true;
+        Ok(picture)
+    }
+
+    /// Creates the picture of the derivative, using the given uris.
+    fn from_derivative_images(
+        image_file_name: &PathBuf,
+        info: &ImageInfo,
+        derivative: &Derivative,
+        uris: ImageUris,
+    ) -> Result<Self, String> {
+        let resized_image_details = ResizedImageDetails::from_derivative(
+            image_file_name,
+            info,
+            derivative,
+        )?;
+        let mut sources = vec![];
+        let mut previous_width = 0;
+        for (idx, details) in resized_image_details.iter().enumerate() {
+            let media_width = if idx + 1 < resized_image_details.len() {
+                MediaWidth::Max(details.width.to_string())
+            } else {
+                MediaWidth::Min((previous_width + 1).to_string())
+            };
+            previous_width = details.width;
+            sources.push(SourceAttributes {
+                media_width: Some(media_width),
+                srcset: uris.get_uri(image_file_name, details)?,
+                density: None,
+            });
+        }
+        let fallback_uri = match sources.last() {
+            Some(s) => s.srcset.clone(),
+            None => {
+                return Err(format!(
+                    "Derivative {} does not contain any width!",
+                    derivative.name
+                ));
+            }
+        };
+
+        let mut picture = Self::from_uris(sources, fallback_uri);
+        picture.animated = info.animated;
+        Ok(picture)
+    }
+
+    /// Replaces the `base_dir` of all uris by the given mountpoint.
+    pub fn apply_mountpoint(
+        &mut self,
+        mountpoint: &PathBuf,
+        base_dir: &PathBuf,
+    ) -> Result<(), String> {
+        let convert = |uri: &str| {
+            let name = crate::path::create_output_file_name_with_output_dir(
+                mountpoint,
+                base_dir,
+                &PathBuf::from(uri),
+            )?;
+            match name.to_str() {
+                Some(v) => Ok(v.to_string()),
+                None => Err(format!("Could not convert {}!", name.display())),
+            }
+        };
+        for source in &mut self.sources {
+            source.srcset = convert(&source.srcset)?;
+        }
+        self.fallback_uri = convert(&self.fallback_uri)?;
+        Ok(())
+    }
+
+    /// Takes over the details of the given metadata that has been determined
+    /// during processing.
+    pub fn apply_metadata(&mut self, metadata: ImageMetadata) {
+        self.placeholder = metadata.placeholder;
+        self.dominant_color = metadata.dominant_color;
+        self.palette = metadata.palette;
+    }
+
+    /// Replaces the directory of all uris by the given base uri, keeping the
+    /// file names. All images of a picture are located in the same directory,
+    /// so the base uri is usually the directory of the original image as it
+    /// is referenced in a document.
+    /// ## Example
+    ///
+    /// ```
+    /// use html5_picture::html5::Picture;
+    ///
+    /// let mut picture = Picture::from_uris(
+    ///     vec![],
+    ///     "dist/images/photo.png".to_string(),
+    /// );
+    /// picture.apply_base_uri("/static/images/");
+    /// assert_eq!(picture.fallback_uri, "/static/images/photo.png");
+    /// ```
+    pub fn apply_base_uri(&mut self, base_uri: &str) {
+        let convert = |uri: &str| {
+            let file_name = match uri.rsplit_once('/') {
+                Some((_, file_name)) => file_name,
+                None => uri,
+            };
+            if base_uri.is_empty() {
+                file_name.to_string()
+            } else {
+                format!("{}/{}", base_uri.trim_end_matches('/'), file_name)
+            }
+        };
+        for source in &mut self.sources {
+            source.srcset = convert(&source.srcset);
+        }
+        self.fallback_uri = convert(&self.fallback_uri);
+    }
+
+    /// Creates a picture from the given sources without reading any image.
+    pub fn from_uris(
+        sources: Vec<SourceAttributes>,
+        fallback_uri: String,
+    ) -> Self {
+        Self {
+            sources,
+            fallback_uri,
+            placeholder: None,
+            dominant_color: None,
+            palette: vec![],
+            palette_background: false,
+            animated: false,
+            pass_through: false,
+        }
+    }
+
+    /// Creates the additional attributes of the `<img>` tag, determined by
+    /// the image metadata.
+    fn get_img_metadata_attributes(&self) -> Vec<(String, String)> {
+        let mut style = vec![];
+        let mut attributes = vec![];
+        if let Some(color) = &self.dominant_color {
+            attributes.push(("data-dominant-color".to_string(), color.clone()));
+            if self.palette_background {
+                style.push(format!("background-color:{}", color));
+            }
+        }
+        if !self.palette.is_empty() {
+            attributes
+                .push(("data-palette".to_string(), self.palette.join(" ")));
+        }
+        match &self.placeholder {
+            Some(Placeholder::DataUri(uri)) => {
+                style.push("background-size:cover".to_string());
+                style.push(format!("background-image:url({})", uri));
+            }
+            Some(Placeholder::BlurHash(hash)) => {
+                attributes.push(("data-blurhash".to_string(), hash.clone()));
+            }
+            None => (),
+        }
+        if !style.is_empty() {
+            attributes.push(("style".to_string(), style.join(";")));
+        }
+        attributes
     }
 
     /// Creates a string that contains the full ```<picture>``` tag. It can
     /// directly be embedded into a webpage.
+    /// ## Example
+    ///
+    /// ```
+    /// use html5_picture::html5::{MediaWidth, Picture, SourceAttributes};
+    ///
+    /// let source = |media_width: Option<MediaWidth>, srcset: &str, density| {
+    ///     SourceAttributes {
+    ///         media_width,
+    ///         srcset: srcset.to_string(),
+    ///         density,
+    ///     }
+    /// };
+    /// let picture = Picture::from_uris(
+    ///     vec![
+    ///         source(Some(MediaWidth::Max("600".to_string())), "a-art600.webp", None),
+    ///         source(None, "a-1x.webp", Some(1)),
+    ///         source(None, "a-2x.webp", Some(2)),
+    ///     ],
+    ///     "a.png".to_string(),
+    /// );
+    /// let html = picture.to_html_string(Some("/assets".to_string()), "A");
+    /// assert_eq!(
+    ///     html,
+    ///     "<picture>\
+    ///     <source media=\"(max-width: 600px)\" srcset=\"/assets/a-art600.webp\">\
+    ///     <source srcset=\"/assets/a-1x.webp 1x, /assets/a-2x.webp 2x\">\
+    ///     <img src=\"/assets/a.png\" alt=\"A\" />\
+    ///     </picture>"
+    /// );
+    /// ```
     pub fn to_html_string(
         &self,
         srcset_prefix: Option<String>,
         alt_text: &str,
     ) -> String {
-        let mut html = String::from("<picture>");
-        let uri_prefix = match &srcset_prefix {
+        self.to_html_string_with_img_attributes(
+            srcset_prefix,
+            &[("alt".to_string(), alt_text.to_string())],
+        )
+    }
+
+    /// Creates a string that contains the full ```<picture>``` tag, adding the
+    /// given attributes to the `<img>` tag. The values are escaped, so they
+    /// are given unescaped. A `src` attribute is ignored, a
+    /// `style` attribute is merged with the style created from the metadata.
+    pub fn to_html_string_with_img_attributes(
+        &self,
+        srcset_prefix: Option<String>,
+        img_attributes: &[(String, String)],
+    ) -> String {
+        let sources = self.get_source_attributes(&srcset_prefix);
+        let img = self.get_img_attributes(&srcset_prefix, img_attributes);
+        TagFormat::Html.render("", self.get_sources(&sources), &img)
+    }
+
+    /// Creates a string that contains the picture in the given tag format.
+    /// Component formats export a component with the given name that passes
+    /// its properties to the `<img>` tag.
+    /// ## Example
+    ///
+    /// ```
+    /// use html5_picture::html5::{Picture, TagFormat};
+    ///
+    /// let picture = Picture::from_uris(vec![], "photo.png".to_string());
+    /// let jsx = picture.to_tag_string(TagFormat::Jsx, "PhotoPicture", None);
+    /// assert!(jsx.contains("export default function PhotoPicture(props)"));
+    /// assert!(jsx.contains(r#"<img src="photo.png" alt="" {...props} />"#));
+    /// ```
+    pub fn to_tag_string(
+        &self,
+        format: TagFormat,
+        component_name: &str,
+        srcset_prefix: Option<String>,
+    ) -> String {
+        let sources = self.get_source_attributes(&srcset_prefix);
+        let img = self.get_img_attributes(
+            &srcset_prefix,
+            &[("alt".to_string(), String::new())],
+        );
+        format.render(component_name, self.get_sources(&sources), &img)
+    }
+
+    /// Returns the given source attributes, or `None` if the image is passed
+    /// through and rendered as plain `<img>` tag.
+    fn get_sources<'a>(
+        &self,
+        sources: &'a [Vec<(String, String)>],
+    ) -> Option<&'a [Vec<(String, String)>]> {
+        match self.pass_through {
+            true => None,
+            false => Some(sources),
+        }
+    }
+
+    /// Creates the attributes of all `<source>` tags. Consecutive sources
+    /// having the same media condition are combined into one srcset.
+    fn get_source_attributes(
+        &self,
+        srcset_prefix: &Option<String>,
+    ) -> Vec<Vec<(String, String)>> {
+        let uri_prefix = match srcset_prefix {
             Some(v) => format!("{}/", v),
             None => String::new(),
         };
-        for src_attrs in &self.sources {
-            let (min_max, value) = match &src_attrs.media_width {
-                MediaWidth::Max(v) => ("max", v),
-                MediaWidth::Min(v) => ("min", v),
-            };
-            html.push_str(&format!(
-                "<source media=\"({}-width: {}px)\" srcset=\"{}{}\">",
-                min_max, value, &uri_prefix, src_attrs.srcset
-            ));
-        }
-        // add fallback image
-        html.push_str(&format!(
-            "<img src=\"{}{}\" alt=\"{}\" />",
-            uri_prefix, self.fallback_uri, alt_text
-        ));
-        html.push_str("</picture>");
-        html
+        let mut sources = vec![];
+        for group in
+            self.sources.chunk_by(|a, b| a.media_width == b.media_width)
+        {
+            let srcset = group
+                .iter()
+                .map(|src_attrs| match src_attrs.density {
+                    Some(d) => {
+                        format!("{}{} {}x", &uri_prefix, src_attrs.srcset, d)
+                    }
+                    None => format!("{}{}", &uri_prefix, src_attrs.srcset),
+                })
+                .collect::<Vec<String>>()
+                .join(", ");
+            let mut attributes = vec![];
+            if let Some(media_width) = &group[0].media_width {
+                let (min_max, value) = match media_width {
+                    MediaWidth::Max(v) => ("max", v),
+                    MediaWidth::Min(v) => ("min", v),
+                };
+                attributes.push((
+                    "media".to_string(),
+                    format!("({}-width: {}px)", min_max, value),
+                ));
+            }
+            attributes.push(("srcset".to_string(), srcset));
+            sources.push(attributes);
+        }
+        sources
+    }
+
+    /// Creates the attributes of the `<img>` tag from the fallback uri, the
+    /// metadata and the given attributes.
+    fn get_img_attributes(
+        &self,
+        srcset_prefix: &Option<String>,
+        img_attributes: &[(String, String)],
+    ) -> Vec<(String, String)> {
+        let src = match srcset_prefix {
+            Some(v) => format!("{}/{}", v, self.fallback_uri),
+            None => self.fallback_uri.clone(),
+        };
+        let mut attributes = vec![("src".to_string(), src)];
+        let mut metadata_attributes = self.get_img_metadata_attributes();
+        for (name, value) in img_attributes {
+            if name.eq_ignore_ascii_case("src") {
+                continue;
+            }
+            let mut value = value.clone();
+            if let Some(idx) = metadata_attributes
+                .iter()
+                .position(|(n, _)| n.eq_ignore_ascii_case(name))
+            {
+                // the given attributes take precedence, except for the style
+                let (_, metadata_value) = metadata_attributes.remove(idx);
+                if name.eq_ignore_ascii_case("style") {
+                    value = format!(
+                        "{};{}",
+                        value.trim_end_matches(';'),
+                        metadata_value
+                    );
+                }
+            }
+            attributes.push((name.clone(), value));
+        }
+        attributes.append(&mut metadata_attributes);
+        attributes
     }
 }
 
@@ -122,71 +720,198 @@ impl Picture {
 /// as possible.
 /// The install_images_into parameter is used to determine which images can be
 /// used.
-#[derive(Debug)]
+/// The register is cheap to clone, all clones share the same pictures.
+#[derive(Clone, Debug)]
 pub struct PictureRegister {
-    register: PathBufPictureRegister,
+    register: Arc<PathBufPictureRegister>,
+    derivatives: Arc<DerivativePictureRegister>,
+    install_dir: PathBuf,
+    mountpoint: Option<PathBuf>,
 }
 
 impl PictureRegister {
     /// Creates a new instance from the given config.
     pub fn from(config: &Config) -> Result<Self, String> {
+        let images_path = Self::get_images_path(config)?;
+        let (register, derivatives) = Self::create_register(config)?;
+        Ok(Self {
+            register: Arc::new(register),
+            derivatives: Arc::new(derivatives),
+            install_dir: images_path.clone(),
+            mountpoint: config.mountpoint.clone(),
+        })
+    }
+
+    /// Returns the folder containing the images of the register. If a cdn
+    /// url is configured, no images are installed, so the input directory
+    /// is used.
+    fn get_images_path(config: &Config) -> Result<&PathBuf, String> {
+        if config.cdn_url.is_some() {
+            return Ok(&config.input_dir);
+        }
         match &config.install_images_into {
-            None => {
-                return Err(
-                    "The install_images_into parameter needs to be set!"
-                        .to_string(),
-                );
-            }
+            None => Err("The install_images_into parameter needs to be set!"
+                .to_string()),
             Some(v) => {
                 if !v.is_dir() {
                     return Err("The install_images_into parameter is not a valid directory".to_string());
                 }
+                Ok(v)
             }
         }
+    }
 
+    /// Creates a new instance from the manifest file written at build time,
+    /// see ```PictureManifest```. No image is read, so the images do not need
+    /// to be available.
+    pub fn from_manifest(file_name: &PathBuf) -> Result<Self, String> {
+        let manifest = PictureManifest::from_file(file_name)?;
+        let derivatives = manifest
+            .derivatives
+            .into_iter()
+            .map(|(image, pictures)| (image, pictures.into_iter().collect()))
+            .collect();
         Ok(Self {
-            register: Self::create_register(&config)?,
+            register: Arc::new(manifest.pictures.into_iter().collect()),
+            derivatives: Arc::new(derivatives),
+            install_dir: manifest.install_dir,
+            mountpoint: manifest.mountpoint,
         })
     }
 
-    /// Creates the register from the given config.
+    /// Creates the manifest containing all pictures of the register.
+    pub fn to_manifest(&self) -> PictureManifest {
+        let derivatives = self
+            .derivatives
+            .iter()
+            .map(|(image, pictures)| {
+                (
+                    image.clone(),
+                    pictures
+                        .iter()
+                        .map(|(name, picture)| (name.clone(), picture.clone()))
+                        .collect(),
+                )
+            })
+            .collect();
+        PictureManifest {
+            install_dir: self.install_dir.clone(),
+            mountpoint: self.mountpoint.clone(),
+            pictures: self
+                .register
+                .iter()
+                .map(|(image, picture)| (image.clone(), picture.clone()))
+                .collect(),
+            derivatives,
+        }
+    }
+
+    /// Creates the register from the given config. The pictures are stored
+    /// by their path relative to the install directory, their uris start
+    /// with the mountpoint if given.
     fn create_register(
         config: &Config,
-    ) -> Result<PathBufPictureRegister, String> {
-        let images_path = match &config.install_images_into {
-            None => {
-                return Err(
-                    "The install_images_into parameter needs to be set!"
-                        .to_string(),
-                );
-            }
-            Some(v) => {
-                if !v.is_dir() {
-                    return Err("The install_images_into parameter is not a valid directory".to_string());
-                }
-                v
+    ) -> Result<(PathBufPictureRegister, DerivativePictureRegister), String>
+    {
+        let images_path = Self::get_images_path(config)?;
+        let mount = |mut picture: Picture| -> Result<Picture, String> {
+            // the urls of the image proxy already contain the mountpoint
+            if let (Some(mountpoint), None) =
+                (&config.mountpoint, &config.cdn_url)
+            {
+                picture.apply_mountpoint(mountpoint, images_path)?;
             }
+            Ok(picture)
         };
 
+        // the metadata is stored by the path relative to the input directory
+        let metadata_dir = crate::path::get_metadata_dir(&config.input_dir)?;
         let mut register = PathBufPictureRegister::new();
-        let png_file_names = crate::collect_png_file_names(&images_path, None);
-        for png in png_file_names {
-            let pic = Picture::from(&png, config.scaled_images_count)?;
-            register.insert(png, pic);
+        let mut derivatives = DerivativePictureRegister::new();
+        let derivative_configs = get_supported_derivatives(config);
+        let image_file_names =
+            crate::collect_image_file_names(images_path, None);
+        for image in image_file_names {
+            let key = crate::path::remove_base_dir(images_path, &image)?;
+            let info = ImageInfo::with_svg_width(&image, config.svg_width)?;
+            let metadata = ImageMetadata::from(&metadata_dir.join(&key))?;
+            let mut pic = mount(Picture::from_config(&image, &info, config)?)?;
+            pic.apply_metadata(metadata.clone());
+            pic.palette_background = config.palette_background;
+            let mut derivative_pictures = HashMap::new();
+            for derivative in derivative_configs {
+                let mut derivative_pic =
+                    mount(Picture::from_derivative_config(
+                        &image, &info, derivative, config,
+                    )?)?;
+                derivative_pic.apply_metadata(metadata.clone());
+                derivative_pic.palette_background = config.palette_background;
+                derivative_pictures
+                    .insert(derivative.name.clone(), derivative_pic);
+            }
+            if !derivative_pictures.is_empty() {
+                derivatives.insert(key.clone(), derivative_pictures);
+            }
+            register.insert(key, pic);
         }
-        Ok(register)
+        Ok((register, derivatives))
     }
 
     /// Returns a reference to the ```Picture``` instance of the given image.
-    /// Please use the original filename of the picture that you want to use,
-    /// for example:
+    /// The image is either given by its path relative to the install
+    /// directory or by its public url starting with the mountpoint, for
+    /// example:
     /// ```ignore
-    /// let p = register_instance.get(&PathBuf::from("assets/image-1.png")).unwrap();
+    /// let p = register_instance.get("some/image-1.png").unwrap();
+    /// let p = register_instance.get("/mountpoint/some/image-1.png").unwrap();
     /// ```
-    pub fn get(&self, image: &PathBuf) -> Result<&Picture, String> {
-        match self.register.get(image) {
+    pub fn get(&self, image: impl AsRef<Path>) -> Result<&Picture, String> {
+        let image = self.get_relative_file_name(image);
+        match self.register.get(&image) {
             None => Err(format!("Image {image:?} not found!")),
             Some(v) => Ok(v),
         }
     }
+
+    /// Returns a reference to the ```Picture``` instance of the derivative
+    /// with the given name of the image, for example:
+    /// ```ignore
+    /// let p = register_instance
+    ///     .get_derivative("some/image-1.png", "thumb")
+    ///     .unwrap();
+    /// ```
+    pub fn get_derivative(
+        &self,
+        image: impl AsRef<Path>,
+        name: &str,
+    ) -> Result<&Picture, String> {
+        let image = self.get_relative_file_name(image);
+        match self.derivatives.get(&image).and_then(|d| d.get(name)) {
+            None => {
+                Err(format!("Derivative {name} of image {image:?} not found!"))
+            }
+            Some(v) => Ok(v),
+        }
+    }
+
+    /// Converts the given image to the key of the register, which is the path
+    /// relative to the install directory. Public urls starting with the
+    /// mountpoint and paths inside the install directory are accepted as
+    /// well.
+    pub fn get_relative_file_name(&self, image: impl AsRef<Path>) -> PathBuf {
+        let image = image.as_ref();
+        let mountpoint = self.mountpoint.as_ref().and_then(|m| m.to_str());
+        if let (Some(mountpoint), Some(uri)) = (mountpoint, image.to_str()) {
+            let mountpoint = mountpoint.trim_end_matches('/');
+            if let Some(relative) = uri.strip_prefix(mountpoint)
+                && relative.starts_with('/')
+            {
+                return PathBuf::from(relative.trim_start_matches('/'));
+            }
+        }
+        match image.strip_prefix(&self.install_dir) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => image.to_path_buf(),
+        }
+    }
 }
@@ -0,0 +1,58 @@
+use {
+    super::Picture,
+    serde::{Deserialize, Serialize},
+    std::{collections::BTreeMap, path::PathBuf},
+};
+
+/// All pictures of a ```PictureRegister``` in serializable form. It is written
+/// at build time, so the register can be loaded by a webserver without
+/// reading any image.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PictureManifest {
+    /// The install directory the pictures have been created for.
+    pub install_dir: PathBuf,
+    /// The mountpoint the uris of the pictures start with.
+    #[serde(default)]
+    pub mountpoint: Option<PathBuf>,
+    /// The pictures by the path of their image relative to the install
+    /// directory.
+    pub pictures: BTreeMap<PathBuf, Picture>,
+    /// The derivative pictures by the path of their image relative to the
+    /// install directory and by their name.
+    #[serde(default)]
+    pub derivatives: BTreeMap<PathBuf, BTreeMap<String, Picture>>,
+}
+
+impl PictureManifest {
+    /// Loads the manifest from the given JSON file.
+    pub fn from_file(file_name: &PathBuf) -> Result<Self, String> {
+        let content = match std::fs::read_to_string(file_name) {
+            Ok(c) => c,
+            Err(msg) => {
+                return Err(format!("{}: {}", file_name.display(), msg));
+            }
+        };
+        match serde_json::from_str(&content) {
+            Ok(v) => Ok(v),
+            Err(msg) => Err(format!("{}: {}", file_name.display(), msg)),
+        }
+    }
+
+    /// Saves the manifest as JSON to the given file. The file is replaced
+    /// atomically, so readers never see a partially written manifest.
+    pub fn save(&self, file_name: &PathBuf) -> Result<(), String> {
+        let content = match serde_json::to_string_pretty(self) {
+            Ok(c) => c,
+            Err(msg) => return Err(msg.to_string()),
+        };
+        let mut temp_file_name = file_name.clone().into_os_string();
+        temp_file_name.push(".tmp");
+        if let Err(msg) = std::fs::write(&temp_file_name, content) {
+            return Err(format!("{}: {}", file_name.display(), msg));
+        }
+        match std::fs::rename(&temp_file_name, file_name) {
+            Ok(()) => Ok(()),
+            Err(msg) => Err(format!("{}: {}", file_name.display(), msg)),
+        }
+    }
+}
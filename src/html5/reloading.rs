@@ -0,0 +1,178 @@
+use {
+    super::PictureRegister,
+    crate::core::Config,
+    arc_swap::ArcSwap,
+    log::error,
+    std::{
+        collections::hash_map::DefaultHasher,
+        hash::{Hash, Hasher},
+        path::PathBuf,
+        sync::{Arc, Mutex},
+        thread::JoinHandle,
+        time::Duration,
+    },
+    walkdir::WalkDir,
+};
+
+/// Defines where a ```ReloadingPictureRegister``` loads its pictures from.
+#[derive(Clone, Debug)]
+pub enum RegisterSource {
+    /// A manifest file, see ```PictureRegister::from_manifest```.
+    Manifest(PathBuf),
+    /// The install directory given by the config, see
+    /// ```PictureRegister::from```.
+    Config(Box<Config>),
+}
+
+impl RegisterSource {
+    /// Loads the register from the source.
+    fn load(&self) -> Result<PictureRegister, String> {
+        match self {
+            Self::Manifest(file_name) => {
+                PictureRegister::from_manifest(file_name)
+            }
+            Self::Config(config) => PictureRegister::from(config),
+        }
+    }
+
+    /// Returns the files and folders that are watched for changes. Besides
+    /// the install directory, the placeholders and palettes are read from
+    /// the metadata directory.
+    fn get_watched_paths(&self) -> Vec<PathBuf> {
+        match self {
+            Self::Manifest(file_name) => vec![file_name.clone()],
+            Self::Config(config) => {
+                let mut paths = vec![];
+                if let Some(install_dir) = &config.install_images_into {
+                    paths.push(install_dir.clone());
+                    if let Ok(metadata_dir) =
+                        crate::path::get_metadata_dir(&config.input_dir)
+                    {
+                        paths.push(metadata_dir);
+                    }
+                }
+                paths
+            }
+        }
+    }
+}
+
+/// Summarizes the state of the watched files to detect changes. It hashes
+/// the path, size and modification time of every file, so a file replaced
+/// by one of the same size and an older modification time is detected.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Fingerprint(u64);
+
+impl Fingerprint {
+    /// Creates the fingerprint of the given files and of all files in the
+    /// given folders.
+    fn from(paths: &[PathBuf]) -> Self {
+        let mut hasher = DefaultHasher::new();
+        for entry in paths
+            .iter()
+            .flat_map(|path| WalkDir::new(path).sort_by_file_name())
+            .filter_map(|e| e.ok())
+        {
+            let metadata = match entry.metadata() {
+                Ok(m) if m.is_file() => m,
+                _ => continue,
+            };
+            entry.path().hash(&mut hasher);
+            metadata.len().hash(&mut hasher);
+            metadata.modified().ok().hash(&mut hasher);
+        }
+        Self(hasher.finish())
+    }
+}
+
+/// A ```PictureRegister``` that can be refreshed while it is in use, for
+/// example by a long running webserver. Lookups take a snapshot of the
+/// current register by ```ReloadingPictureRegister::load``` and never block,
+/// a reload replaces the register atomically once it has been fully loaded.
+/// ## Example
+///
+/// ```ignore
+/// let register = ReloadingPictureRegister::new(RegisterSource::Manifest(
+///     PathBuf::from("dist/pictures.json"),
+/// ))?;
+/// // checks the manifest for changes every ten seconds
+/// register.spawn_polling(Duration::from_secs(10));
+///
+/// let html = register.load().get("hero.png")?.to_html_string(None, "Hero");
+/// ```
+pub struct ReloadingPictureRegister {
+    register: ArcSwap<PictureRegister>,
+    source: RegisterSource,
+    fingerprint: Mutex<Fingerprint>,
+}
+
+impl ReloadingPictureRegister {
+    /// Creates a new instance and loads the register from the given source.
+    pub fn new(source: RegisterSource) -> Result<Arc<Self>, String> {
+        let fingerprint = Fingerprint::from(&source.get_watched_paths());
+        let register = source.load()?;
+        Ok(Arc::new(Self {
+            register: ArcSwap::from_pointee(register),
+            source,
+            fingerprint: Mutex::new(fingerprint),
+        }))
+    }
+
+    /// Returns the current register. The returned register stays valid and
+    /// unchanged even if a reload happens in the meantime.
+    pub fn load(&self) -> Arc<PictureRegister> {
+        self.register.load_full()
+    }
+
+    /// Loads the register from its source and replaces the current one. On
+    /// failure the current register is kept.
+    pub fn reload(&self) -> Result<(), String> {
+        let register = self.source.load()?;
+        self.register.store(Arc::new(register));
+        Ok(())
+    }
+
+    /// Reloads the register if the watched manifest, install directory or
+    /// metadata directory has changed since the last load. Returns true if
+    /// the register has been replaced.
+    pub fn reload_if_changed(&self) -> Result<bool, String> {
+        let paths = self.source.get_watched_paths();
+        if paths.is_empty() {
+            return Ok(false);
+        }
+        // only one reload at a time, lookups are not affected by this lock
+        let mut fingerprint = match self.fingerprint.lock() {
+            Ok(f) => f,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let current = Fingerprint::from(&paths);
+        if current == *fingerprint {
+            return Ok(false);
+        }
+        self.reload()?;
+        *fingerprint = current;
+        Ok(true)
+    }
+
+    /// Spawns a thread that checks the source for changes in the given
+    /// interval and reloads the register if required. The thread stops
+    /// as soon as the register has been dropped.
+    pub fn spawn_polling(
+        self: &Arc<Self>,
+        interval: Duration,
+    ) -> JoinHandle<()> {
+        let register = Arc::downgrade(self);
+        std::thread::spawn(move || {
+            loop {
+                std::thread::sleep(interval);
+                let register = match register.upgrade() {
+                    Some(r) => r,
+                    None => return,
+                };
+                if let Err(msg) = register.reload_if_changed() {
+                    error!("Reloading the picture register failed: {}", msg);
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,305 @@
+use {
+    clap::ArgEnum,
+    html_escape::encode_double_quoted_attribute,
+    serde::{Deserialize, Serialize},
+};
+
+/// Attribute names that are named differently in JSX.
+const JSX_ATTRIBUTE_NAMES: [(&str, &str); 8] = [
+    ("class", "className"),
+    ("for", "htmlFor"),
+    ("srcset", "srcSet"),
+    ("crossorigin", "crossOrigin"),
+    ("referrerpolicy", "referrerPolicy"),
+    ("fetchpriority", "fetchPriority"),
+    ("usemap", "useMap"),
+    ("tabindex", "tabIndex"),
+];
+
+/// The syntax the picture tags are written in.
+#[derive(ArgEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum TagFormat {
+    /// A plain HTML `<picture>` tag.
+    Html,
+    /// A React component in JSX syntax.
+    Jsx,
+    /// A React component in TSX syntax.
+    Tsx,
+    /// A Vue single file component.
+    Vue,
+    /// A Svelte component.
+    Svelte,
+}
+
+impl TagFormat {
+    /// Returns the file extension of files in this format.
+    pub fn get_extension(&self) -> &'static str {
+        match self {
+            Self::Html => "html",
+            Self::Jsx => "jsx",
+            Self::Tsx => "tsx",
+            Self::Vue => "vue",
+            Self::Svelte => "svelte",
+        }
+    }
+
+    /// Creates the name of the component for the given file stem, for
+    /// example `PhotoThumbPicture` for `photo-thumb`.
+    /// ## Example
+    ///
+    /// ```
+    /// use html5_picture::html5::TagFormat;
+    ///
+    /// assert_eq!(TagFormat::get_component_name("photo-thumb"), "PhotoThumbPicture");
+    /// assert_eq!(TagFormat::get_component_name("2021_team"), "Image2021TeamPicture");
+    /// ```
+    pub fn get_component_name(file_stem: &str) -> String {
+        let mut name: String = file_stem
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|part| !part.is_empty())
+            .map(|part| {
+                let mut chars = part.chars();
+                // unwrap allowed, empty parts are filtered
+                let first = chars.next().unwrap().to_ascii_uppercase();
+                std::iter::once(first).chain(chars).collect::<String>()
+            })
+            .collect();
+        if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            name.insert_str(0, "Image");
+        }
+        name.push_str("Picture");
+        name
+    }
+
+    /// Renders the `<picture>` tag consisting of the given `<source>` and
+    /// `<img>` attributes in this format. Without sources, a plain `<img>`
+    /// tag is rendered.
+    pub(crate) fn render(
+        &self,
+        component_name: &str,
+        sources: Option<&[Vec<(String, String)>]>,
+        img: &[(String, String)],
+    ) -> String {
+        match self {
+            Self::Html => {
+                let img = format!("<img{} />", to_html_attributes(img));
+                let sources = match sources {
+                    Some(s) => s,
+                    None => return img,
+                };
+                let mut html = String::from("<picture>");
+                for source in sources {
+                    html.push_str(&format!(
+                        "<source{}>",
+                        to_html_attributes(source)
+                    ));
+                }
+                html.push_str(&img);
+                html.push_str("</picture>");
+                html
+            }
+            Self::Jsx | Self::Tsx => {
+                let mut component = String::new();
+                let props = if *self == Self::Tsx {
+                    component.push_str(
+                        "import type { ImgHTMLAttributes } from \"react\";\n\n",
+                    );
+                    "props: ImgHTMLAttributes<HTMLImageElement>"
+                } else {
+                    "props"
+                };
+                component.push_str(&format!(
+                    "export default function {}({}) {{\n  return (\n",
+                    component_name, props
+                ));
+                let img =
+                    format!("<img{} {{...props}} />", to_jsx_attributes(img));
+                match sources {
+                    Some(sources) => {
+                        component.push_str("    <picture>\n");
+                        for source in sources {
+                            component.push_str(&format!(
+                                "      <source{} />\n",
+                                to_jsx_attributes(source)
+                            ));
+                        }
+                        component.push_str(&format!(
+                            "      {}\n    </picture>\n",
+                            img
+                        ));
+                    }
+                    None => component.push_str(&format!("    {}\n", img)),
+                }
+                component.push_str("  );\n}\n");
+                component
+            }
+            Self::Vue => {
+                // the attributes of the component are passed to the <img> tag
+                // instead of the root element
+                let mut component = String::from(
+                    "<script setup>\ndefineOptions({ inheritAttrs: false });\n</script>\n\n<template>\n",
+                );
+                let img = format!(
+                    "<img{} v-bind=\"$attrs\">",
+                    to_html_attributes(img)
+                );
+                match sources {
+                    Some(sources) => {
+                        component.push_str("  <picture>\n");
+                        for source in sources {
+                            component.push_str(&format!(
+                                "    <source{}>\n",
+                                to_html_attributes(source)
+                            ));
+                        }
+                        component
+                            .push_str(&format!("    {}\n  </picture>\n", img));
+                    }
+                    None => component.push_str(&format!("  {}\n", img)),
+                }
+                component.push_str("</template>\n");
+                component
+            }
+            Self::Svelte => {
+                let mut component = String::from(
+                    "<script>\n  let props = $props();\n</script>\n\n",
+                );
+                let img =
+                    format!("<img{} {{...props}}>", to_svelte_attributes(img));
+                match sources {
+                    Some(sources) => {
+                        component.push_str("<picture>\n");
+                        for source in sources {
+                            component.push_str(&format!(
+                                "  <source{}>\n",
+                                to_svelte_attributes(source)
+                            ));
+                        }
+                        component.push_str(&format!("  {}\n</picture>\n", img));
+                    }
+                    None => component.push_str(&format!("{}\n", img)),
+                }
+                component
+            }
+        }
+    }
+}
+
+/// Joins the given attributes to a string that can be placed into a HTML tag.
+fn to_html_attributes(attributes: &[(String, String)]) -> String {
+    attributes
+        .iter()
+        .map(|(name, value)| {
+            format!(" {}=\"{}\"", name, encode_double_quoted_attribute(value))
+        })
+        .collect()
+}
+
+/// Joins the given attributes to a string that can be placed into a JSX tag.
+/// The names are converted to their JSX equivalent and the style is passed
+/// as object.
+fn to_jsx_attributes(attributes: &[(String, String)]) -> String {
+    attributes
+        .iter()
+        .map(|(name, value)| {
+            let name = name.to_ascii_lowercase();
+            if name == "style" {
+                return format!(" style={{{}}}", to_jsx_style(value));
+            }
+            let jsx_name =
+                match JSX_ATTRIBUTE_NAMES.iter().find(|(n, _)| *n == name) {
+                    Some((_, jsx_name)) => jsx_name.to_string(),
+                    // data and aria attributes keep their names
+                    None if name.starts_with("data-")
+                        || name.starts_with("aria-") =>
+                    {
+                        name
+                    }
+                    None => to_camel_case(&name),
+                };
+            // JSX decodes HTML entities in string literals
+            format!(
+                " {}=\"{}\"",
+                jsx_name,
+                encode_double_quoted_attribute(value)
+            )
+        })
+        .collect()
+}
+
+/// Converts the given CSS declarations to a JavaScript object, e.g.
+/// `background-color:#fff` to `{ backgroundColor: "#fff" }`.
+fn to_jsx_style(style: &str) -> String {
+    let declarations: Vec<String> = split_declarations(style)
+        .iter()
+        .filter_map(|declaration| declaration.split_once(':'))
+        .map(|(property, value)| {
+            let property = property.trim();
+            let property = match property.starts_with("--") {
+                // custom properties need to be quoted
+                true => format!("\"{}\"", property),
+                false => to_camel_case(property),
+            };
+            // unwrap allowed, serializing a string does not fail
+            let value = serde_json::to_string(value.trim()).unwrap();
+            format!("{}: {}", property, value)
+        })
+        .collect();
+    format!("{{ {} }}", declarations.join(", "))
+}
+
+/// Splits the given CSS declarations at semicolons that are not part of a
+/// function like `url(data:image/webp;base64,...)`.
+fn split_declarations(style: &str) -> Vec<&str> {
+    let mut declarations = vec![];
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (idx, c) in style.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ';' if depth == 0 => {
+                declarations.push(&style[start..idx]);
+                start = idx + 1;
+            }
+            _ => (),
+        }
+    }
+    declarations.push(&style[start..]);
+    declarations
+        .into_iter()
+        .filter(|d| !d.trim().is_empty())
+        .collect()
+}
+
+/// Joins the given attributes to a string that can be placed into a Svelte
+/// tag. Curly braces are escaped as they start an expression in Svelte.
+fn to_svelte_attributes(attributes: &[(String, String)]) -> String {
+    attributes
+        .iter()
+        .map(|(name, value)| {
+            let value = encode_double_quoted_attribute(value)
+                .replace('{', "&#123;")
+                .replace('}', "&#125;");
+            format!(" {}=\"{}\"", name, value)
+        })
+        .collect()
+}
+
+/// Converts a hyphenated name to camel case, e.g. `background-color` to
+/// `backgroundColor`.
+fn to_camel_case(name: &str) -> String {
+    let mut camel_case = String::with_capacity(name.len());
+    let mut uppercase_next = false;
+    for c in name.chars() {
+        if c == '-' {
+            uppercase_next = !camel_case.is_empty();
+        } else if uppercase_next {
+            camel_case.push(c.to_ascii_uppercase());
+            uppercase_next = false;
+        } else {
+            camel_case.push(c);
+        }
+    }
+    camel_case
+}
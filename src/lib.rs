@@ -5,14 +5,15 @@
 //! can be used on webpages. Also offers the possibility to convert them into webp
 //! format and is able to create `<picture>` tags for the given images.
 //!
-//! Currently this crate is only capable of converting `png` files to webp using
-//! `cwebp`.
-//! So make sure that webp is installed on your computer.
+//! Currently this crate is only capable of converting `png` and `gif` files,
+//! including animated ones, and `svg` files to webp. The images are decoded,
+//! resized and encoded in process by the bundled `libwebp`, so no external
+//! tool is required. SVGs are rasterized before, or optionally copied as they
+//! are.
 //!
 //! ## Installation
 //!
-//! The binary can be installed via `cargo install html5-picture`. As stated
-//! before, make sure webp is installed before using.
+//! The binary can be installed via `cargo install html5-picture`.
 //!
 //! ## Usage
 //!
@@ -60,6 +61,67 @@
 //! html5-picture ./assets 4 -i ./dist -p ./html5-tags -m /some/web-server/mountpoint
 //! ```
 //!
+//! ### Low quality image placeholders
+//! Using `--placeholder webp` embeds a tiny blurred webp version of the image
+//! as background of the `<img>` tag. `--placeholder blurhash` adds a
+//! `data-blurhash` attribute instead that can be decoded by your JavaScript.
+//!
+//! ```bash
+//! html5-picture ./assets 4 -i ./dist -p ./html5-tags --placeholder webp
+//! ```
+//!
+//! ### Color and metadata
+//! Images are converted to sRGB, or to Display P3 by `--color-space
+//! display-p3`, and rotated as given by their EXIF orientation. The EXIF and
+//! XMP metadata is removed unless `--metadata copyright` or
+//! `--metadata keep-except-gps` is given, the GPS coordinates are removed in
+//! any case. `--palette 5` extracts the dominant color and a palette of five
+//! colors, which are stored next to the placeholder in the metadata directory
+//! and added as data attributes to the `<img>` tag. `--palette-background`
+//! uses the dominant color as its `background-color` as well.
+//!
+//! ### Art direction
+//! Crops per breakpoint are defined in a sidecar file, e.g. `hero.picture.json`
+//! next to `hero.png`. Each crop is saved as `hero-art<max_width>.webp` and
+//! added as `<source media="(max-width: ...)">` to the tag. Crops that only
+//! define an aspect ratio keep the focal point of the image in view.
+//!
+//! ### Fixed aspect ratio derivatives
+//! `--derivative "thumb: 1:1 @ 200,400"` additionally creates
+//! `photo-thumb-w200.webp` and `photo-thumb-w400.webp` as well as the tag file
+//! `photo-thumb.html`.
+//!
+//! ### Pixel density mode
+//! For images displayed with a fixed CSS width, `--density-width 48` creates
+//! `avatar-1x.webp`, `avatar-2x.webp` and `avatar-3x.webp` and a tag using
+//! `srcset="avatar-1x.webp 1x, avatar-2x.webp 2x, ..."`.
+//!
+//! ### Rewriting documents
+//! `--rewrite-html <folder>` and `--rewrite-markdown <folder>` replace the
+//! `<img>` tags and Markdown image references to processed images by
+//! `<picture>` tags. The `mdbook-html5-picture` binary does the same as
+//! preprocessor of an mdBook. With `--rewrite-only`, the documents are
+//! rewritten against the images installed by a previous run without
+//! converting anything.
+//!
+//! ```bash
+//! html5-picture ./assets 3 -i ./dist/assets -m /assets --rewrite-html ./dist
+//! ```
+//!
+//! ### Image proxy
+//! With `--cdn-url`, no image is encoded locally. The sources of the tags point
+//! to an image proxy like imgproxy or thumbor instead.
+//!
+//! ```bash
+//! html5-picture ./assets 3 -p ./html5-tags -m /assets \
+//!     --cdn-url "https://img.example.com/{signature}/rs:fit:{width}/plain/{source_url}@webp"
+//! ```
+//!
+//! ### Web server configuration
+//! `--server-config nginx|apache|caddy` writes a snippet to
+//! `--server-config-file` that serves the webp version of an image if the
+//! client accepts it, without any `<picture>` tag.
+//!
 //! ### Read input files by JSON
 //! **Upcoming feature.**
 //! The pictures can be defined using JSON format. `html5-picture` will read it
@@ -70,14 +132,16 @@
 #[deny(rustdoc::private_intra_doc_links)]
 use {
     crate::core::{
-        Config, State, cleanup_temporary_directory, collect_file_names,
-        copy_originals_to_output, create_all_output_directories,
-        install_images_into, process_images, save_html_picture_tags,
+        Config, State, check_output_file_names, cleanup_temporary_directory,
+        collect_file_names, copy_originals_to_output,
+        create_all_output_directories, install_images_into, process_images,
+        rewrite_html_files, rewrite_markdown_files, save_html_picture_tags,
+        save_picture_manifest, save_server_config,
     },
     indicatif::ProgressBar,
     log::error,
     queue::Queue,
-    std::path::PathBuf,
+    std::path::{Path, PathBuf},
     walkdir::WalkDir,
 };
 
@@ -99,6 +163,30 @@ pub mod fs;
 /// HTML5 related functions, such as creation of picture tags.
 pub mod html5;
 
+/// Replaces image references in existing documents by picture tags.
+pub mod rewrite;
+
+/// Preprocessor for mdBook that converts the images of a book and replaces
+/// their references in the chapters by picture tags.
+pub mod mdbook;
+
+/// Creates the urls of images that are resized on the fly by an image proxy
+/// like imgproxy or thumbor.
+pub mod cdn;
+
+/// Configuration snippets for nginx, Apache and Caddy that serve the webp
+/// versions of the images by content negotiation.
+pub mod server_config;
+
+/// Integrations into template engines, enabled by the cargo features `tera`
+/// and `askama`.
+pub mod template;
+
+/// Middleware for tower based webservers like axum, enabled by the cargo
+/// feature `tower`.
+#[cfg(feature = "tower")]
+pub mod middleware;
+
 /// Determines if the given input filename contains a .png extension.
 pub fn is_png(input: &PathBuf) -> bool {
     match input.extension() {
@@ -110,10 +198,54 @@ pub fn is_png(input: &PathBuf) -> bool {
     }
 }
 
+/// Determines if the given input filename contains a .gif extension.
+pub fn is_gif(input: &Path) -> bool {
+    match input.extension() {
+        Some(s) => match s.to_str() {
+            None => false,
+            Some(v) => v == "gif",
+        },
+        None => false,
+    }
+}
+
+/// Determines if the given input filename contains a .svg extension.
+pub fn is_svg(input: &Path) -> bool {
+    match input.extension() {
+        Some(s) => match s.to_str() {
+            None => false,
+            Some(v) => v == "svg",
+        },
+        None => false,
+    }
+}
+
 /// Collects all png file names that are stored in the ```input_dir```.
 pub fn collect_png_file_names(
     input_dir: &PathBuf,
     progressbar: Option<ProgressBar>,
+) -> Vec<PathBuf> {
+    collect_matching_file_names(input_dir, progressbar, is_png)
+}
+
+/// Collects all png, gif and svg file names that are stored in the
+/// ```input_dir```. These are the images that are converted, png and gif
+/// can be animated. The posters of animated images are skipped, in case
+/// they are installed into the ```input_dir```.
+pub fn collect_image_file_names(
+    input_dir: &PathBuf,
+    progressbar: Option<ProgressBar>,
+) -> Vec<PathBuf> {
+    collect_matching_file_names(input_dir, progressbar, |f| {
+        (is_png(f) || is_gif(f) || is_svg(f)) && !utils::animation::is_poster(f)
+    })
+}
+
+/// Collects all file names in the ```input_dir``` matching the given filter.
+fn collect_matching_file_names(
+    input_dir: &PathBuf,
+    progressbar: Option<ProgressBar>,
+    filter: fn(&PathBuf) -> bool,
 ) -> Vec<PathBuf> {
     let mut file_names = vec![];
     for entry in WalkDir::new(&input_dir) {
@@ -130,7 +262,7 @@ pub fn collect_png_file_names(
             pb.tick();
         }
 
-        if !is_png(&entry) {
+        if !filter(&entry) {
             continue;
         }
         file_names.push(entry);
@@ -138,6 +270,31 @@ pub fn collect_png_file_names(
     file_names
 }
 
+/// Collects all file names in ```dir``` that have one of the given extensions.
+pub fn collect_file_names_with_extensions(
+    dir: &PathBuf,
+    extensions: &[&str],
+) -> Vec<PathBuf> {
+    let mut file_names = vec![];
+    for entry in WalkDir::new(dir) {
+        let entry = match entry {
+            Ok(e) => e.into_path(),
+            Err(msg) => {
+                error!("{}", msg);
+                continue;
+            }
+        };
+        let has_extension = match entry.extension().and_then(|e| e.to_str()) {
+            Some(e) => extensions.iter().any(|ext| e.eq_ignore_ascii_case(ext)),
+            None => false,
+        };
+        if entry.is_file() && has_extension {
+            file_names.push(entry);
+        }
+    }
+    file_names
+}
+
 /// The main function of the binary. Executes all required steps for copying,
 /// conversion and installation of the source images.
 pub fn run(config: Config) {
@@ -152,26 +309,79 @@ pub fn run(config: Config) {
         }
         _ => (),
     }
+    if let Err(msg) = check_output_file_names(
+        &collect_image_file_names(&config.input_dir, None),
+        &config.derivatives,
+    ) {
+        error!("{}", msg);
+        return;
+    }
+
+    if config.rewrite_only {
+        run_rewrite_only(config);
+        return;
+    }
 
     // add all default processes
     let mut q: Queue<fn(&mut State)> = Queue::new();
     q.queue(collect_file_names).unwrap();
-    q.queue(create_all_output_directories).unwrap();
-    q.queue(copy_originals_to_output).unwrap();
 
-    // finally add processing step
-    q.queue(process_images).unwrap();
+    // the image proxy resizes the images, so nothing is encoded locally
+    let is_local = config.cdn_url.is_none();
+    if is_local {
+        q.queue(create_all_output_directories).unwrap();
+        q.queue(copy_originals_to_output).unwrap();
+
+        // finally add processing step
+        q.queue(process_images).unwrap();
+    }
 
     // optional steps
-    if let Some(_) = &config.install_images_into {
+    if is_local && config.install_images_into.is_some() {
         q.queue(install_images_into).unwrap();
     }
     if let Some(_) = &config.picture_tags_output_folder {
         q.queue(save_html_picture_tags).unwrap();
     }
+    if config.manifest.is_some() {
+        q.queue(save_picture_manifest).unwrap();
+    }
+    if config.server_config.is_some() {
+        q.queue(save_server_config).unwrap();
+    }
+    if config.rewrite_html.is_some() {
+        q.queue(rewrite_html_files).unwrap();
+    }
+    if config.rewrite_markdown.is_some() {
+        q.queue(rewrite_markdown_files).unwrap();
+    }
+
+    // clean up the temporary directory as the final step
+    if is_local {
+        q.queue(cleanup_temporary_directory).unwrap();
+    }
+
+    let mut s = State::new(config, q.len());
 
-    // Always clean up temporary directory as the final step
-    q.queue(cleanup_temporary_directory).unwrap();
+    while let Some(step_function) = s.dequeue(&mut q) {
+        step_function(&mut s);
+    }
+}
+
+/// Rewrites the documents against the images that are already installed,
+/// without converting any image.
+fn run_rewrite_only(config: Config) {
+    let mut q: Queue<fn(&mut State)> = Queue::new();
+    if config.rewrite_html.is_some() {
+        q.queue(rewrite_html_files).unwrap();
+    }
+    if config.rewrite_markdown.is_some() {
+        q.queue(rewrite_markdown_files).unwrap();
+    }
+    if q.is_empty() {
+        error!("Nothing to rewrite, use --rewrite-html or --rewrite-markdown!");
+        return;
+    }
 
     let mut s = State::new(config, q.len());
 
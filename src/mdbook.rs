@@ -0,0 +1,291 @@
+//! The preprocessor is enabled in the `book.toml` of the
+//! [mdBook](https://rust-lang.github.io/mdBook/):
+//!
+//! ```toml
+//! [preprocessor.html5-picture]
+//! # folder of the images, relative to the src folder of the book
+//! assets = "images"
+//! scaled-images-count = 3
+//! quality = 70
+//! placeholder = "webp"
+//! derivatives = ["thumb: 1:1 @ 200,400"]
+//! ```
+//!
+//! The converted images are installed next to the source images so mdBook
+//! copies them to the output folder. Images are only converted again if the
+//! source image or its sidecar file is newer than any of its webp versions,
+//! or if `force-overwrite` is set.
+
+use {
+    crate::{
+        core::{
+            Config, State, check_output_file_names,
+            cleanup_temporary_directory, create_all_output_directories,
+            install_images_into, is_passed_through, process_images,
+        },
+        html5::{Picture, PictureRegister},
+        rewrite::{PictureResolver, rewrite_markdown},
+        utils::{ImageInfo, Sidecar},
+    },
+    clap::Parser,
+    serde_json::{Map, Value},
+    std::path::{Path, PathBuf},
+};
+
+/// The name of the preprocessor table in the `book.toml`.
+pub const PREPROCESSOR_NAME: &str = "html5-picture";
+
+/// Default amount of scaled images if not given in the `book.toml`.
+const DEFAULT_SCALED_IMAGES_COUNT: u64 = 3;
+
+/// Options of the preprocessor table that are passed as value.
+const VALUE_OPTIONS: [&str; 3] = ["placeholder", "palette", "density-width"];
+
+/// Returns true if the preprocessor supports the given renderer. The
+/// `<picture>` tags are only useful for the HTML renderer.
+pub fn supports_renderer(renderer: &str) -> bool {
+    renderer == "html"
+}
+
+/// Runs the preprocessor. The input is the JSON that mdBook passes on stdin,
+/// consisting of the preprocessor context and the book. Returns the JSON of
+/// the modified book that needs to be written to stdout.
+pub fn run_preprocessor(input: &str) -> Result<String, String> {
+    let (context, mut book): (Value, Value) = match serde_json::from_str(input)
+    {
+        Ok(v) => v,
+        Err(msg) => return Err(msg.to_string()),
+    };
+    let root = match context["root"].as_str() {
+        Some(r) => PathBuf::from(r),
+        None => return Err("The book root is missing!".to_string()),
+    };
+    let src_dir =
+        root.join(context["config"]["book"]["src"].as_str().unwrap_or("src"));
+    let empty_table = Map::new();
+    let table = match context["config"]["preprocessor"][PREPROCESSOR_NAME]
+        .as_object()
+    {
+        Some(t) => t,
+        None => &empty_table,
+    };
+
+    let config = create_config(&src_dir, table)?;
+    let reprocess_all = config.force_overwrite;
+    convert_images(config.clone(), reprocess_all)?;
+
+    let register = PictureRegister::from(&config)?;
+    let resolver = PictureResolver::new(&register, &config);
+    // mdBook up to 0.4 calls the chapter list sections, later versions items
+    for key in ["sections", "items"] {
+        if let Some(items) = book.get_mut(key) {
+            rewrite_items(items, &src_dir, &resolver);
+        }
+    }
+    match serde_json::to_string(&book) {
+        Ok(v) => Ok(v),
+        Err(msg) => Err(msg.to_string()),
+    }
+}
+
+/// Creates the config from the preprocessor table of the `book.toml`. The
+/// images are installed into the assets folder itself.
+fn create_config(
+    src_dir: &Path,
+    table: &Map<String, Value>,
+) -> Result<Config, String> {
+    let assets_dir = match table.get("assets") {
+        Some(Value::String(s)) => src_dir.join(s),
+        Some(_) => {
+            return Err("The assets option needs to be a string!".to_string());
+        }
+        None => src_dir.to_path_buf(),
+    };
+    let assets_dir = match assets_dir.to_str() {
+        Some(a) => a.to_string(),
+        None => return Err("utf-8 check failed for assets folder".to_string()),
+    };
+    let scaled_images_count = match table.get("scaled-images-count") {
+        Some(v) => to_option_value(v),
+        None => DEFAULT_SCALED_IMAGES_COUNT.to_string(),
+    };
+    let mut args = vec![
+        PREPROCESSOR_NAME.to_string(),
+        assets_dir.clone(),
+        scaled_images_count,
+        "-i".to_string(),
+        assets_dir,
+        // only outdated images are processed, so their webp files need to be
+        // replaced during installation
+        "-f".to_string(),
+    ];
+    if let Some(quality) = table.get("quality") {
+        args.push("-q".to_string());
+        args.push(to_option_value(quality));
+    }
+    for option in VALUE_OPTIONS {
+        if let Some(value) = table.get(option) {
+            args.push(format!("--{}", option));
+            args.push(to_option_value(value));
+        }
+    }
+    if let Some(derivatives) =
+        table.get("derivatives").and_then(Value::as_array)
+    {
+        for derivative in derivatives {
+            args.push("--derivative".to_string());
+            args.push(to_option_value(derivative));
+        }
+    }
+    if let Some(Value::Bool(true)) = table.get("single-threaded") {
+        args.push("-s".to_string());
+    }
+    let mut config = match Config::try_parse_from(args) {
+        Ok(c) => c,
+        Err(msg) => return Err(msg.to_string()),
+    };
+    config.force_overwrite =
+        matches!(table.get("force-overwrite"), Some(Value::Bool(true)));
+    config.palette_background =
+        matches!(table.get("palette-background"), Some(Value::Bool(true)));
+    Ok(config)
+}
+
+/// Converts the images of the assets folder that have no up to date webp
+/// version, or all images if `reprocess_all` is set.
+fn convert_images(
+    mut config: Config,
+    reprocess_all: bool,
+) -> Result<(), String> {
+    if !config.input_dir.is_dir() {
+        return Err(format!(
+            "The assets folder {} does not exist!",
+            config.input_dir.display()
+        ));
+    }
+    let file_names = crate::collect_image_file_names(&config.input_dir, None);
+    check_output_file_names(&file_names, &config.derivatives)?;
+    // SVGs that are passed through have no webp version
+    let file_names: Vec<PathBuf> = file_names
+        .into_iter()
+        .filter(|f| !is_passed_through(&config, f))
+        .filter(|f| reprocess_all || is_outdated(&config, f))
+        .collect();
+    if file_names.is_empty() {
+        return Ok(());
+    }
+    config.force_overwrite = true;
+    let steps: [fn(&mut State); 4] = [
+        create_all_output_directories,
+        process_images,
+        install_images_into,
+        cleanup_temporary_directory,
+    ];
+    let mut state = State::new(config, steps.len());
+    state.file_names_to_convert = file_names;
+    for (idx, step) in steps.iter().enumerate() {
+        state.current_step = idx + 1;
+        step(&mut state);
+    }
+    Ok(())
+}
+
+/// Returns true if any of the images created for the given image is missing
+/// or older than the image or its sidecar file.
+fn is_outdated(config: &Config, image_file_name: &PathBuf) -> bool {
+    let modified = |file_name: &Path| {
+        std::fs::metadata(file_name).and_then(|m| m.modified()).ok()
+    };
+    let sidecar = Sidecar::get_file_name(image_file_name).ok();
+    let source = [Some(image_file_name.as_path()), sidecar.as_deref()]
+        .into_iter()
+        .flatten()
+        .filter_map(modified)
+        .max();
+    let (source, outputs) =
+        match (source, get_output_file_names(config, image_file_name)) {
+            (Some(source), Ok(outputs)) => (source, outputs),
+            _ => return true,
+        };
+    outputs.iter().any(|output| match modified(output) {
+        Some(output) => output < source,
+        None => true,
+    })
+}
+
+/// Collects the file names of all images that are created for the given
+/// image, including its derivatives and the poster of animated images.
+fn get_output_file_names(
+    config: &Config,
+    image_file_name: &PathBuf,
+) -> Result<Vec<PathBuf>, String> {
+    let info = ImageInfo::with_svg_width(image_file_name, config.svg_width)?;
+    let mut pictures =
+        vec![Picture::from_config(image_file_name, &info, config)?];
+    for derivative in &config.derivatives {
+        pictures.push(Picture::from_derivative_config(
+            image_file_name,
+            &info,
+            derivative,
+            config,
+        )?);
+    }
+    let mut output_file_names = vec![];
+    for picture in pictures {
+        output_file_names.extend(
+            picture.sources.into_iter().map(|s| PathBuf::from(s.srcset)),
+        );
+        if picture.animated {
+            output_file_names.push(PathBuf::from(picture.fallback_uri));
+        }
+    }
+    Ok(output_file_names)
+}
+
+/// Rewrites the content of all chapters in the given list of book items.
+fn rewrite_items(
+    items: &mut Value,
+    src_dir: &PathBuf,
+    resolver: &PictureResolver,
+) {
+    let items = match items.as_array_mut() {
+        Some(i) => i,
+        None => return,
+    };
+    for item in items {
+        if let Some(chapter) = item.get_mut("Chapter") {
+            rewrite_chapter(chapter, src_dir, resolver);
+        }
+    }
+}
+
+/// Replaces the image references of the given chapter and its sub chapters.
+fn rewrite_chapter(
+    chapter: &mut Value,
+    src_dir: &PathBuf,
+    resolver: &PictureResolver,
+) {
+    // draft chapters do not have a path
+    if let Some(path) = chapter["path"].as_str() {
+        let mut document_dir = src_dir.join(path);
+        document_dir.pop();
+        if let Some(content) = chapter["content"].as_str() {
+            let content = rewrite_markdown(content, |src| {
+                resolver.resolve(src, &document_dir)
+            });
+            chapter["content"] = Value::String(content);
+        }
+    }
+    if let Some(sub_items) = chapter.get_mut("sub_items") {
+        rewrite_items(sub_items, src_dir, resolver);
+    }
+}
+
+/// Converts the given value of the preprocessor table to a command line
+/// option value.
+fn to_option_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
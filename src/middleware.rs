@@ -0,0 +1,324 @@
+//! Serves the best image format a client supports as an alternative to
+//! `<picture>` tags, for example `/assets/photo.webp` for a request to
+//! `/assets/photo.png` if the `Accept` header contains `image/webp`.
+//! Other requests are passed to the wrapped service unchanged.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! let register = PictureRegister::from_manifest(&PathBuf::from("dist/pictures.json"))?;
+//! let app = Router::new()
+//!     .nest_service("/assets", ServeDir::new("dist/assets"))
+//!     .layer(
+//!         PictureNegotiationLayer::new(register, PathBuf::from("dist/assets"))
+//!             .with_client_hints(true),
+//!     );
+//! ```
+
+use {
+    crate::html5::{Picture, PictureRegister},
+    bytes::Bytes,
+    futures::future::BoxFuture,
+    http::{HeaderMap, HeaderValue, Method, Request, Response, header},
+    http_body_util::{Either, Full},
+    std::{
+        path::{Path, PathBuf},
+        sync::Arc,
+        task::{Context, Poll},
+    },
+    tower_layer::Layer,
+    tower_service::Service,
+};
+
+/// File extensions and content types of the formats that are served instead
+/// of the original image, in order of preference.
+const VARIANTS: [(&str, &str); 2] =
+    [("avif", "image/avif"), ("webp", "image/webp")];
+
+/// Client hint headers containing the width the image is displayed with.
+const WIDTH_HINTS: [&str; 2] = ["sec-ch-width", "width"];
+
+/// The settings shared by all instances of the service.
+#[derive(Clone, Debug)]
+struct Negotiation {
+    register: PictureRegister,
+    install_dir: PathBuf,
+    client_hints: bool,
+}
+
+impl Negotiation {
+    /// Returns the value of the `Vary` header of negotiated responses.
+    fn get_vary_header(&self) -> HeaderValue {
+        match self.client_hints {
+            true => HeaderValue::from_static("Accept, Sec-CH-Width, Width"),
+            false => HeaderValue::from_static("Accept"),
+        }
+    }
+
+    /// Determines the file and content type that is served for the given
+    /// image and request headers. Returns None if the client does not
+    /// support any of the available variants.
+    fn negotiate(
+        &self,
+        picture: &Picture,
+        uri_path: &str,
+        headers: &HeaderMap,
+    ) -> Option<(PathBuf, &'static str)> {
+        let image = self
+            .install_dir
+            .join(self.register.get_relative_file_name(uri_path));
+        let width = match self.client_hints {
+            true => get_width_hint(headers),
+            false => None,
+        };
+        VARIANTS
+            .iter()
+            .filter(|(_, content_type)| accepts(headers, content_type))
+            .map(|(extension, content_type)| {
+                let file_name = match (*extension, width) {
+                    ("webp", Some(width)) => {
+                        get_scaled_variant(picture, &image, width)
+                    }
+                    _ => image.with_extension(extension),
+                };
+                (file_name, *content_type)
+            })
+            .find(|(file_name, _)| file_name.is_file())
+    }
+}
+
+/// A tower ```Layer``` that serves the images of the register in the best
+/// format the client supports. The variants are read from the install
+/// directory, the `Vary` header is set on all responses for known images.
+#[derive(Clone, Debug)]
+pub struct PictureNegotiationLayer {
+    negotiation: Negotiation,
+}
+
+impl PictureNegotiationLayer {
+    /// Creates a new instance. Requests are matched against the public urls
+    /// of the register, the variants are served from the given install
+    /// directory.
+    pub fn new(register: PictureRegister, install_dir: PathBuf) -> Self {
+        Self {
+            negotiation: Negotiation {
+                register,
+                install_dir,
+                client_hints: false,
+            },
+        }
+    }
+
+    /// Enables the `Sec-CH-Width` and `Width` client hints. If given, the
+    /// smallest scaled webp image that is at least as wide is served. The
+    /// client needs to be asked for the hints by an `Accept-CH` header.
+    pub fn with_client_hints(mut self, client_hints: bool) -> Self {
+        self.negotiation.client_hints = client_hints;
+        self
+    }
+}
+
+impl<S> Layer<S> for PictureNegotiationLayer {
+    type Service = PictureNegotiation<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        PictureNegotiation {
+            inner,
+            negotiation: Arc::new(self.negotiation.clone()),
+        }
+    }
+}
+
+/// The service created by ```PictureNegotiationLayer```.
+#[derive(Clone, Debug)]
+pub struct PictureNegotiation<S> {
+    inner: S,
+    negotiation: Arc<Negotiation>,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for PictureNegotiation<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+    ReqBody: Send + 'static,
+{
+    type Response = Response<Either<Full<Bytes>, ResBody>>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        // the instance that has been polled ready is used for this request
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let negotiation = Arc::clone(&self.negotiation);
+        Box::pin(async move {
+            let is_read =
+                matches!(*request.method(), Method::GET | Method::HEAD);
+            let path = decode_path(request.uri().path());
+            let picture = match path
+                .as_ref()
+                .map(|path| negotiation.register.get(path))
+            {
+                Some(Ok(p)) if is_read => p,
+                _ => {
+                    let response = inner.call(request).await?;
+                    return Ok(response.map(Either::Right));
+                }
+            };
+            let vary = negotiation.get_vary_header();
+            // unwrap allowed, the picture has been found by the path
+            let variant = match negotiation.negotiate(
+                picture,
+                path.as_ref().unwrap(),
+                request.headers(),
+            ) {
+                Some((file_name, content_type)) => {
+                    let is_head = request.method() == Method::HEAD;
+                    read_variant(&file_name, is_head)
+                        .await
+                        .map(|variant| (variant, content_type))
+                }
+                None => None,
+            };
+            if let Some(((body, content_length), content_type)) = variant {
+                let mut response = Response::new(Either::Left(Full::new(body)));
+                let headers = response.headers_mut();
+                headers.insert(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static(content_type),
+                );
+                headers.insert(
+                    header::CONTENT_LENGTH,
+                    HeaderValue::from(content_length),
+                );
+                headers.insert(header::VARY, vary);
+                return Ok(response);
+            }
+            // the original image is served, but it still depends on the
+            // request headers
+            let mut response = inner.call(request).await?;
+            response.headers_mut().append(header::VARY, vary);
+            Ok(response.map(Either::Right))
+        })
+    }
+}
+
+/// Reads the body and the content length of the variant with the given file
+/// name. Only the size is read for HEAD requests, their body is empty.
+async fn read_variant(file_name: &Path, is_head: bool) -> Option<(Bytes, u64)> {
+    match is_head {
+        true => {
+            let metadata = tokio::fs::metadata(file_name).await.ok()?;
+            Some((Bytes::new(), metadata.len()))
+        }
+        false => {
+            let content = tokio::fs::read(file_name).await.ok()?;
+            let content_length = content.len() as u64;
+            Some((Bytes::from(content), content_length))
+        }
+    }
+}
+
+/// Decodes the percent encoded path of a request uri. Returns None if it is
+/// invalid or contains a `..` segment, so the variants are never looked up
+/// outside of the install directory.
+fn decode_path(path: &str) -> Option<String> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        if bytes[idx] != b'%' {
+            decoded.push(bytes[idx]);
+            idx += 1;
+            continue;
+        }
+        let hex = bytes.get(idx + 1..idx + 3)?;
+        if !hex.iter().all(u8::is_ascii_hexdigit) {
+            return None;
+        }
+        // unwrap allowed, the digits are checked above
+        let hex = std::str::from_utf8(hex).unwrap();
+        decoded.push(u8::from_str_radix(hex, 16).unwrap());
+        idx += 3;
+    }
+    let decoded = String::from_utf8(decoded).ok()?;
+    match decoded.split(['/', '\\']).any(|segment| segment == "..") {
+        true => None,
+        false => Some(decoded),
+    }
+}
+
+/// Returns true if the `Accept` header explicitly contains the given content
+/// type. Wildcards are ignored, as they do not guarantee support of modern
+/// formats.
+fn accepts(headers: &HeaderMap, content_type: &str) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|media_range| {
+            let mut parameters = media_range.split(';');
+            let media_type = parameters.next().unwrap_or_default().trim();
+            let quality = parameters
+                .filter_map(|p| p.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            media_type.eq_ignore_ascii_case(content_type) && quality > 0.0
+        })
+}
+
+/// Returns the width in physical pixels given by the client hints.
+fn get_width_hint(headers: &HeaderMap) -> Option<u32> {
+    WIDTH_HINTS.iter().find_map(|name| {
+        let width = headers
+            .get(*name)?
+            .to_str()
+            .ok()?
+            .trim()
+            .parse::<f64>()
+            .ok()?;
+        match width > 0.0 {
+            true => Some(width.ceil() as u32),
+            false => None,
+        }
+    })
+}
+
+/// Returns the smallest scaled webp version of the image that is at least
+/// `width` wide, or the full size webp version if there is none.
+fn get_scaled_variant(picture: &Picture, image: &Path, width: u32) -> PathBuf {
+    let prefix = match image.file_stem().and_then(|f| f.to_str()) {
+        Some(stem) => format!("{}-w", stem),
+        None => return image.with_extension("webp"),
+    };
+    // the scaled images are named <stem>-w<width>.webp
+    let mut widths: Vec<u32> = picture
+        .sources
+        .iter()
+        .filter(|source| source.density.is_none())
+        .filter_map(|source| {
+            let file_name = source.srcset.rsplit('/').next()?;
+            file_name
+                .strip_prefix(&prefix)?
+                .strip_suffix(".webp")?
+                .parse()
+                .ok()
+        })
+        .collect();
+    widths.sort_unstable();
+    match widths.into_iter().find(|w| *w >= width) {
+        Some(w) => image.with_file_name(format!("{}{}.webp", prefix, w)),
+        None => image.with_extension("webp"),
+    }
+}
@@ -81,6 +81,56 @@ pub fn get_output_working_dir(input_dir: &PathBuf) -> Result<PathBuf, String> {
     )))
 }
 
+/// Generates the name of the directory that keeps the metadata determined
+/// during processing. It is located next to the input directory, so it is
+/// neither installed nor removed with the working directory.
+/// ## Example
+///
+/// ```
+/// use {
+///     html5_picture::path::get_metadata_dir,
+///     std::path::PathBuf,
+/// };
+///
+/// let input = PathBuf::from("../assets");
+/// let metadata_dir = get_metadata_dir(&input).unwrap();
+/// assert_eq!(
+///     metadata_dir.to_str().unwrap(),
+///     "../.assets-html5picture-metadata"
+/// );
+/// ```
+pub fn get_metadata_dir(input_dir: &PathBuf) -> Result<PathBuf, String> {
+    let mut metadata_dir = get_output_working_dir(input_dir)?.into_os_string();
+    metadata_dir.push("-metadata");
+    Ok(PathBuf::from(metadata_dir))
+}
+
+/// Generates a file name that is stored in the metadata directory, see
+/// `get_metadata_dir`.
+/// ## Example
+///
+/// ```
+/// use {
+///     html5_picture::path::create_metadata_file_name,
+///     std::path::PathBuf,
+/// };
+///
+/// let base_dir = PathBuf::from("../assets");
+/// let input_file = PathBuf::from("../assets/some/picture.png");
+/// let file_name = create_metadata_file_name(&base_dir, &input_file).unwrap();
+/// assert_eq!(
+///     file_name.to_str().unwrap(),
+///     "../.assets-html5picture-metadata/some/picture.png"
+/// );
+/// ```
+pub fn create_metadata_file_name(
+    base_dir: &PathBuf,
+    input_file: &PathBuf,
+) -> Result<PathBuf, String> {
+    let relative_file_name = remove_base_dir(base_dir, input_file)?;
+    Ok(get_metadata_dir(base_dir)?.join(relative_file_name))
+}
+
 /// Removes the given base directory from the given input file.
 /// ## Example
 ///
@@ -0,0 +1,143 @@
+//! Replaces image references in existing documents by `<picture>` tags.
+
+use {
+    crate::{
+        core::Config,
+        html5::{Picture, PictureRegister},
+    },
+    std::path::{Component, Path, PathBuf},
+};
+
+mod html;
+mod markdown;
+
+pub use {html::rewrite_html, markdown::rewrite_markdown};
+
+/// Resolves the images that are referenced in documents to the pictures
+/// stored in a ```PictureRegister```.
+pub struct PictureResolver<'a> {
+    register: &'a PictureRegister,
+    config: &'a Config,
+}
+
+impl<'a> PictureResolver<'a> {
+    /// Creates a new instance.
+    pub fn new(register: &'a PictureRegister, config: &'a Config) -> Self {
+        Self { register, config }
+    }
+
+    /// Returns the picture of the image referenced by `src` in a document
+    /// located in `document_dir`. The `src` is either a public uri starting
+    /// with the mountpoint or a path relative to the document that points into
+    /// the input or install directory. The uris of the returned picture are
+    /// located in the same directory as `src`.
+    pub fn resolve(&self, src: &str, document_dir: &Path) -> Option<Picture> {
+        let uri = match src.find(['?', '#']) {
+            Some(idx) => &src[..idx],
+            None => src,
+        };
+        if uri.is_empty()
+            || uri.contains("://")
+            || uri.starts_with("//")
+            || uri.starts_with("data:")
+        {
+            return None;
+        }
+        let (relative_file_name, is_installed) =
+            self.get_relative_file_name(uri, document_dir)?;
+        let mut picture = self.register.get(&relative_file_name).ok()?.clone();
+        if self.config.cdn_url.is_some() {
+            // the urls point to the image proxy, independent of the document
+            return Some(picture);
+        }
+        let install_dir = self.config.install_images_into.as_ref()?;
+        if is_installed {
+            let base_uri = match uri.rsplit_once('/') {
+                Some((base_uri, _)) => base_uri,
+                None => "",
+            };
+            picture.apply_base_uri(base_uri);
+            picture.fallback_uri = src.to_string();
+        } else {
+            // the reference points into the input directory, but the
+            // converted images are only available in the install directory
+            let mut image_dir = normalize(install_dir).join(relative_file_name);
+            image_dir.pop();
+            let base_uri =
+                get_relative_path(&normalize(document_dir), &image_dir);
+            picture.apply_base_uri(base_uri.to_str()?);
+        }
+        Some(picture)
+    }
+
+    /// Determines the file name of the referenced image relative to the
+    /// input directory. The returned flag is true if the reference points
+    /// to the installed image.
+    fn get_relative_file_name(
+        &self,
+        uri: &str,
+        document_dir: &Path,
+    ) -> Option<(PathBuf, bool)> {
+        if let Some(mountpoint) = &self.config.mountpoint {
+            let mountpoint = mountpoint.to_str()?.trim_end_matches('/');
+            if let Some(relative) = uri.strip_prefix(mountpoint)
+                && relative.starts_with('/')
+            {
+                return Some((
+                    PathBuf::from(relative.trim_start_matches('/')),
+                    true,
+                ));
+            }
+        }
+        if uri.starts_with('/') {
+            return None;
+        }
+        let file_name = normalize(&document_dir.join(uri));
+        if let Some(install_dir) = &self.config.install_images_into
+            && let Ok(relative) = file_name.strip_prefix(normalize(install_dir))
+        {
+            return Some((relative.to_path_buf(), true));
+        }
+        match file_name.strip_prefix(normalize(&self.config.input_dir)) {
+            Ok(relative) => Some((relative.to_path_buf(), false)),
+            Err(_) => None,
+        }
+    }
+}
+
+/// Lexically calculates the path of `to` relative to `from`. Both paths need
+/// to be normalized.
+fn get_relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from: Vec<Component> = from.components().collect();
+    let to: Vec<Component> = to.components().collect();
+    let common = from
+        .iter()
+        .zip(to.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut relative = PathBuf::new();
+    for _ in common..from.len() {
+        relative.push("..");
+    }
+    for component in &to[common..] {
+        relative.push(component);
+    }
+    relative
+}
+
+/// Lexically removes `.` and resolves `..` components of the given path.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push("..");
+                }
+            }
+            c => normalized.push(c),
+        }
+    }
+    normalized
+}
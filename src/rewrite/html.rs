@@ -0,0 +1,98 @@
+use {
+    crate::html5::Picture,
+    html_escape::decode_html_entities,
+    lol_html::{
+        RewriteStrSettings, element, end_tag, html_content::ContentType,
+        rewrite_str,
+    },
+    std::{cell::Cell, rc::Rc},
+};
+
+/// Replaces all `<img>` tags of the given HTML document whose `src` can be
+/// resolved to a picture by the full `<picture>` tag. All attributes of the
+/// `<img>` tag are kept. Images that are already part of a `<picture>` tag,
+/// unknown images and the rest of the document stay untouched.
+/// ## Example
+///
+/// ```
+/// use html5_picture::{html5::Picture, rewrite::rewrite_html};
+///
+/// let html = r#"<p><img src="/assets/a.png" alt="A" class="hero"><img src="b.png"></p>"#;
+/// let output = rewrite_html(html, |src| match src {
+///     "/assets/a.png" => Some(Picture::from_uris(vec![], src.to_string())),
+///     _ => None,
+/// })
+/// .unwrap();
+/// assert_eq!(
+///     output,
+///     r#"<p><picture><img src="/assets/a.png" alt="A" class="hero" /></picture><img src="b.png"></p>"#
+/// );
+///
+/// // attribute values are escaped again, whatever quotes they were written in
+/// let html = r#"<img src="a.png" alt='Tom &amp; "Jerry"'>"#;
+/// let output = rewrite_html(html, |src| {
+///     Some(Picture::from_uris(vec![], src.to_string()))
+/// })
+/// .unwrap();
+/// assert_eq!(
+///     output,
+///     r#"<picture><img src="a.png" alt="Tom &amp; &quot;Jerry&quot;" /></picture>"#
+/// );
+/// ```
+pub fn rewrite_html(
+    html: &str,
+    resolve: impl Fn(&str) -> Option<Picture>,
+) -> Result<String, String> {
+    let picture_depth = Rc::new(Cell::new(0usize));
+    let picture_depth_start = Rc::clone(&picture_depth);
+    let result = rewrite_str(
+        html,
+        RewriteStrSettings {
+            element_content_handlers: vec![
+                element!("picture", move |el| {
+                    picture_depth_start.set(picture_depth_start.get() + 1);
+                    let picture_depth_end = Rc::clone(&picture_depth_start);
+                    el.on_end_tag(end_tag!(move |_| {
+                        picture_depth_end
+                            .set(picture_depth_end.get().saturating_sub(1));
+                        Ok(())
+                    }))?;
+                    Ok(())
+                }),
+                element!("img[src]", |el| {
+                    if picture_depth.get() > 0 {
+                        return Ok(());
+                    }
+                    // unwrap allowed, the selector only matches img with src
+                    let src = el.get_attribute("src").unwrap();
+                    if let Some(picture) = resolve(&decode_html_entities(&src))
+                    {
+                        // the values are given as written in the document,
+                        // they are escaped again by the picture tag
+                        let attributes: Vec<(String, String)> = el
+                            .attributes()
+                            .iter()
+                            .map(|a| {
+                                let value = a.value();
+                                (a.name(), decode_html_entities(&value).into())
+                            })
+                            .collect();
+                        el.replace(
+                            &picture.to_html_string_with_img_attributes(
+                                None,
+                                &attributes,
+                            ),
+                            ContentType::Html,
+                        );
+                    }
+                    Ok(())
+                }),
+            ],
+            ..RewriteStrSettings::new()
+        },
+    );
+    match result {
+        Ok(v) => Ok(v),
+        Err(msg) => Err(msg.to_string()),
+    }
+}
@@ -0,0 +1,107 @@
+use {
+    crate::html5::Picture,
+    pulldown_cmark::{Event, Options, Parser, Tag, TagEnd},
+    std::ops::Range,
+};
+
+/// An image reference found in a Markdown document.
+struct ImageReference {
+    range: Range<usize>,
+    src: String,
+    title: String,
+    alt: String,
+}
+
+/// Replaces all image references of the given Markdown document whose source
+/// can be resolved to a picture by the full `<picture>` tag. The alt text of
+/// the reference becomes the `alt` attribute, its title the `title`
+/// attribute. The rest of the document is kept byte for byte.
+/// ## Example
+///
+/// ```
+/// use html5_picture::{html5::Picture, rewrite::rewrite_markdown};
+///
+/// let md = "# Title\n\nSee ![A *cat*](cat.png \"Cat\") and ![](dog.png).\n\n    ![code](cat.png)\n";
+/// let output = rewrite_markdown(md, |src| match src {
+///     "cat.png" => Some(Picture::from_uris(vec![], src.to_string())),
+///     _ => None,
+/// });
+/// assert_eq!(
+///     output,
+///     "# Title\n\nSee <picture><img src=\"cat.png\" alt=\"A cat\" title=\"Cat\" /></picture> and ![](dog.png).\n\n    ![code](cat.png)\n"
+/// );
+/// ```
+pub fn rewrite_markdown(
+    markdown: &str,
+    resolve: impl Fn(&str) -> Option<Picture>,
+) -> String {
+    let mut output = String::with_capacity(markdown.len());
+    let mut position = 0;
+    for image in find_images(markdown) {
+        let picture = match resolve(&image.src) {
+            Some(p) => p,
+            None => continue,
+        };
+        let mut attributes = vec![("alt".to_string(), image.alt.clone())];
+        if !image.title.is_empty() {
+            attributes.push(("title".to_string(), image.title.clone()));
+        }
+        output.push_str(&markdown[position..image.range.start]);
+        output.push_str(
+            &picture.to_html_string_with_img_attributes(None, &attributes),
+        );
+        position = image.range.end;
+    }
+    output.push_str(&markdown[position..]);
+    output
+}
+
+/// Collects all image references of the given document in order of their
+/// appearance. Images nested in the alt text of another image are skipped.
+fn find_images(markdown: &str) -> Vec<ImageReference> {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS;
+    let mut images = vec![];
+    let mut current: Option<ImageReference> = None;
+    let mut depth = 0usize;
+    for (event, range) in Parser::new_ext(markdown, options).into_offset_iter()
+    {
+        match event {
+            Event::Start(Tag::Image {
+                dest_url, title, ..
+            }) => {
+                if depth == 0 {
+                    current = Some(ImageReference {
+                        range,
+                        src: dest_url.to_string(),
+                        title: title.to_string(),
+                        alt: String::new(),
+                    });
+                }
+                depth += 1;
+            }
+            Event::End(TagEnd::Image) => {
+                depth = depth.saturating_sub(1);
+                if depth == 0
+                    && let Some(image) = current.take()
+                {
+                    images.push(image);
+                }
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(image) = current.as_mut() {
+                    image.alt.push_str(&text);
+                }
+            }
+            Event::SoftBreak | Event::HardBreak => {
+                if let Some(image) = current.as_mut() {
+                    image.alt.push(' ');
+                }
+            }
+            _ => (),
+        }
+    }
+    images
+}
@@ -0,0 +1,168 @@
+//! Every request for an original image is answered by its avif or webp
+//! version if the client lists the format in its `Accept` header and the
+//! version exists in the install directory. The responses carry
+//! `Vary: Accept` and a `Cache-Control` header, so shared caches store the
+//! formats separately.
+
+use {
+    clap::ArgEnum,
+    serde::{Deserialize, Serialize},
+    std::path::Path,
+};
+
+/// Extensions and content types of the variants that are served instead of
+/// the original image, in order of preference.
+const VARIANTS: [(&str, &str); 2] =
+    [("avif", "image/avif"), ("webp", "image/webp")];
+
+/// Value of the `Cache-Control` header set on all image responses.
+const CACHE_CONTROL: &str = "public, max-age=604800";
+
+/// The web server the configuration snippet is written for.
+#[derive(ArgEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum WebServer {
+    /// `map` blocks for the http context and a `location` using `try_files`.
+    Nginx,
+    /// `mod_rewrite` rules for the virtual host.
+    Apache,
+    /// Named matchers with `rewrite` and `header` directives.
+    Caddy,
+}
+
+impl WebServer {
+    /// Renders the configuration snippet. The `mountpoint` is the url path the
+    /// `install_dir` is served at, `extensions` are the file extensions of the
+    /// original images and `image_count` is the amount of processed images.
+    pub fn render(
+        &self,
+        install_dir: &Path,
+        mountpoint: &str,
+        extensions: &[String],
+        image_count: usize,
+    ) -> String {
+        let mountpoint = mountpoint.trim_end_matches('/');
+        let extensions = extensions
+            .iter()
+            .map(|e| escape_regex(e))
+            .collect::<Vec<String>>()
+            .join("|");
+        let mut config = format!(
+            "# Generated by html5-picture for {} images in {}.\n",
+            image_count,
+            install_dir.display()
+        );
+        match self {
+            Self::Nginx => {
+                config.push_str(
+                    "# The map blocks belong to the http context, the location to the\n# server block of your site.\n",
+                );
+                for (extension, content_type) in VARIANTS {
+                    config.push_str(&format!(
+                        "map $http_accept $html5_picture_{0} {{\n    default \"\";\n    \"~*{1}\" \".{0}\";\n}}\n\n",
+                        extension, content_type
+                    ));
+                }
+                config.push_str(&format!(
+                    "location ~ ^{}/(?<html5_picture_name>.+)\\.(?<html5_picture_extension>{})$ {{\n",
+                    escape_regex(mountpoint),
+                    extensions
+                ));
+                config.push_str(&format!(
+                    "    root {};\n    add_header Vary Accept;\n    add_header Cache-Control \"{}\";\n    try_files",
+                    quote(install_dir),
+                    CACHE_CONTROL
+                ));
+                for (extension, _) in VARIANTS {
+                    config.push_str(&format!(
+                        " /$html5_picture_name$html5_picture_{}",
+                        extension
+                    ));
+                }
+                config.push_str(
+                    " /$html5_picture_name.$html5_picture_extension =404;\n}\n",
+                );
+            }
+            Self::Apache => {
+                config.push_str(
+                    "# Place it into the virtual host of your site, requires mod_rewrite,\n# mod_headers and mod_mime.\n",
+                );
+                for (extension, content_type) in VARIANTS {
+                    config.push_str(&format!(
+                        "AddType {} .{}\n",
+                        content_type, extension
+                    ));
+                }
+                config.push_str(&format!(
+                    "\n<Directory {}>\n    RewriteEngine On\n",
+                    quote(install_dir)
+                ));
+                for (extension, content_type) in VARIANTS {
+                    config.push_str(&format!(
+                        "\n    RewriteCond %{{HTTP_ACCEPT}} {1}\n    RewriteCond %{{REQUEST_FILENAME}} ^(.+)\\.({2})$\n    RewriteCond %1.{0} -f\n    RewriteRule ^(.+)\\.({2})$ $1.{0} [T={1},L]\n",
+                        extension, content_type, extensions
+                    ));
+                }
+                config.push_str(&format!(
+                    "\n    <FilesMatch \"\\.({}|{})$\">\n        Header append Vary Accept\n        Header set Cache-Control \"{}\"\n    </FilesMatch>\n</Directory>\n",
+                    extensions,
+                    VARIANTS.map(|(extension, _)| extension).join("|"),
+                    CACHE_CONTROL
+                ));
+            }
+            Self::Caddy => {
+                config.push_str(&format!(
+                    "# Place it into the site block that serves {}/ from the install\n# directory.\n",
+                    mountpoint
+                ));
+                let pattern = format!(
+                    "^{}/(.+)\\.({})$",
+                    escape_regex(mountpoint),
+                    extensions
+                );
+                // the header directive is applied before the rewrite, so it
+                // matches the requested path
+                config.push_str(&format!(
+                    "@html5_picture path_regexp {}\nheader @html5_picture {{\n    Vary Accept\n    Cache-Control \"{}\"\n}}\n",
+                    pattern, CACHE_CONTROL
+                ));
+                for (extension, content_type) in VARIANTS {
+                    config.push_str(&format!(
+                        "\n@html5_picture_{0} {{\n    header Accept *{1}*\n    path_regexp html5_picture_{0} {2}\n    file {{\n        root {3}\n        try_files /{{re.html5_picture_{0}.1}}.{0}\n    }}\n}}\nrewrite @html5_picture_{0} {4}/{{re.html5_picture_{0}.1}}.{0}\n",
+                        extension,
+                        content_type,
+                        pattern,
+                        quote(install_dir),
+                        mountpoint
+                    ));
+                }
+            }
+        }
+        config
+    }
+
+    /// Returns the file name the snippet is written to if none is given.
+    pub fn get_default_file_name(&self) -> &'static str {
+        match self {
+            Self::Nginx => "html5-picture.nginx.conf",
+            Self::Apache => "html5-picture.apache.conf",
+            Self::Caddy => "html5-picture.Caddyfile",
+        }
+    }
+}
+
+/// Quotes the given path so it can contain whitespace.
+fn quote(path: &Path) -> String {
+    format!("\"{}\"", path.display().to_string().replace('"', "\\\""))
+}
+
+/// Escapes the characters that have a special meaning in regular expressions.
+fn escape_regex(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\.+*?()|[]{}^$".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
@@ -0,0 +1,30 @@
+//! Both integrations are backed by a shared [PictureRegister], so
+//! they can be created for every template without reloading the pictures.
+//! Images are given relative to the install directory or by their public url,
+//! the uris of the pictures start with the mountpoint.
+
+#[cfg(feature = "askama")]
+mod askama;
+#[cfg(feature = "tera")]
+mod tera;
+
+#[cfg(feature = "askama")]
+pub use askama::{PictureHelper, PictureHtml};
+#[cfg(feature = "tera")]
+pub use tera::PictureFunction;
+
+/// Renders the `<picture>` tag of the given image, or of its derivative. The
+/// attributes are added to the `<img>` tag.
+#[cfg(any(feature = "tera", feature = "askama"))]
+fn render_picture(
+    register: &crate::html5::PictureRegister,
+    image: &str,
+    derivative: Option<&str>,
+    img_attributes: &[(String, String)],
+) -> Result<String, String> {
+    let picture = match derivative {
+        Some(name) => register.get_derivative(image, name)?,
+        None => register.get(image)?,
+    };
+    Ok(picture.to_html_string_with_img_attributes(None, img_attributes))
+}
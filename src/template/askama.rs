@@ -0,0 +1,100 @@
+use {
+    super::render_picture, crate::html5::PictureRegister,
+    ::askama::filters::HtmlSafe, std::fmt,
+};
+
+/// Renders `<picture>` tags of images stored in the register in
+/// [Askama](https://askama.rs) templates. Add it as field to your template
+/// and call its methods, the output is not escaped.
+/// ## Example
+///
+/// ```ignore
+/// #[derive(Template)]
+/// #[template(path = "index.html")]
+/// struct Index {
+///     pictures: PictureHelper,
+/// }
+///
+/// let index = Index {
+///     pictures: PictureHelper::new(register.clone()),
+/// };
+/// ```
+///
+/// ```jinja
+/// {{ pictures.picture("hero.png", "Our team")? }}
+/// {{ pictures.picture_with("hero.png", "Our team", [("class", "hero")])? }}
+/// {{ pictures.derivative("hero.png", "thumb", "Our team")? }}
+/// ```
+#[derive(Clone)]
+pub struct PictureHelper {
+    register: PictureRegister,
+}
+
+impl PictureHelper {
+    /// Creates a new instance.
+    pub fn new(register: PictureRegister) -> Self {
+        Self { register }
+    }
+
+    /// Renders the `<picture>` tag of the given image.
+    pub fn picture(
+        &self,
+        path: &str,
+        alt: &str,
+    ) -> ::askama::Result<PictureHtml> {
+        self.picture_with(path, alt, &[])
+    }
+
+    /// Renders the `<picture>` tag of the given image, adding the given
+    /// attributes to the `<img>` tag.
+    pub fn picture_with(
+        &self,
+        path: &str,
+        alt: &str,
+        attributes: &[(&str, &str)],
+    ) -> ::askama::Result<PictureHtml> {
+        self.render(path, None, alt, attributes)
+    }
+
+    /// Renders the `<picture>` tag of the derivative with the given name.
+    pub fn derivative(
+        &self,
+        path: &str,
+        name: &str,
+        alt: &str,
+    ) -> ::askama::Result<PictureHtml> {
+        self.render(path, Some(name), alt, &[])
+    }
+
+    fn render(
+        &self,
+        path: &str,
+        derivative: Option<&str>,
+        alt: &str,
+        attributes: &[(&str, &str)],
+    ) -> ::askama::Result<PictureHtml> {
+        let mut img_attributes = vec![("alt".to_string(), alt.to_string())];
+        img_attributes.extend(
+            attributes
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string())),
+        );
+        match render_picture(&self.register, path, derivative, &img_attributes)
+        {
+            Ok(html) => Ok(PictureHtml(html)),
+            Err(msg) => Err(::askama::Error::custom(msg)),
+        }
+    }
+}
+
+/// A rendered `<picture>` tag. It is HTML safe, so Askama does not escape it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PictureHtml(pub String);
+
+impl fmt::Display for PictureHtml {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl HtmlSafe for PictureHtml {}
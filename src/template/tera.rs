@@ -0,0 +1,74 @@
+use {
+    super::render_picture,
+    crate::html5::PictureRegister,
+    ::tera::{Function, Value},
+    std::collections::HashMap,
+};
+
+/// Arguments of the function that are not added to the `<img>` tag.
+const RESERVED_ARGUMENTS: [&str; 2] = ["path", "derivative"];
+
+/// A [Tera](https://keats.github.io/tera/) function that renders the
+/// `<picture>` tag of an image stored in the register. The `path` of the image
+/// is required, the name of a `derivative` is optional. All other arguments are
+/// added as attributes to the `<img>` tag. The output is marked as safe.
+/// ## Example
+///
+/// ```ignore
+/// let mut tera = Tera::new("templates/**/*.html")?;
+/// tera.register_function("picture", PictureFunction::new(register.clone()));
+/// ```
+///
+/// ```jinja
+/// {{ picture(path="hero.png", alt="Our team", sizes="100vw", class="hero") }}
+/// {{ picture(path="hero.png", derivative="thumb", alt="Our team") }}
+/// ```
+#[derive(Clone)]
+pub struct PictureFunction {
+    register: PictureRegister,
+}
+
+impl PictureFunction {
+    /// Creates a new instance.
+    pub fn new(register: PictureRegister) -> Self {
+        Self { register }
+    }
+}
+
+impl Function for PictureFunction {
+    fn call(&self, args: &HashMap<String, Value>) -> ::tera::Result<Value> {
+        let path = match args.get("path").and_then(Value::as_str) {
+            Some(p) => p,
+            None => {
+                return Err(
+                    "The picture function requires a path argument!".into()
+                );
+            }
+        };
+        let derivative = args.get("derivative").and_then(Value::as_str);
+        let mut attributes: Vec<(String, String)> = args
+            .iter()
+            .filter(|(name, _)| !RESERVED_ARGUMENTS.contains(&name.as_str()))
+            .filter_map(|(name, value)| {
+                let value = match value {
+                    Value::String(s) => s.clone(),
+                    // boolean attributes are only added if true
+                    Value::Bool(true) => String::new(),
+                    Value::Bool(false) | Value::Null => return None,
+                    v => v.to_string(),
+                };
+                Some((name.clone(), value))
+            })
+            .collect();
+        // the arguments are unordered, sort them for a stable output
+        attributes.sort();
+        match render_picture(&self.register, path, derivative, &attributes) {
+            Ok(html) => Ok(Value::String(html)),
+            Err(msg) => Err(msg.into()),
+        }
+    }
+
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
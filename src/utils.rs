@@ -1,9 +1,28 @@
 use indicatif::ProgressBar;
 
+pub mod animation;
+pub mod color;
+pub mod crop;
+pub mod derivative;
+mod image_info;
+mod image_metadata;
 pub mod imageops;
+pub mod metadata;
+pub mod palette;
+mod placeholder;
+pub mod resample;
 mod resized_image_details;
+mod sidecar;
+pub mod svg;
+pub mod xmp;
 
-pub use resized_image_details::ResizedImageDetails;
+pub use {
+    image_info::ImageInfo,
+    image_metadata::ImageMetadata,
+    placeholder::{Placeholder, PlaceholderType},
+    resized_image_details::ResizedImageDetails,
+    sidecar::{ArtDirection, Sidecar},
+};
 
 /// Creates a spinner that can be used to indicate progress.
 pub fn create_spinner() -> ProgressBar {
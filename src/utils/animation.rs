@@ -0,0 +1,237 @@
+//! Animated source images, i.e. animated gifs and APNGs. Their frames are
+//! converted to animated webp images, the first frame is saved as static
+//! poster that is used as fallback of the `<picture>` tag.
+
+use {
+    crate::utils::{
+        crop::CropRect,
+        resample::{PreparedImage, ResizeParameter},
+    },
+    image::{
+        AnimationDecoder, DynamicImage, Frames, ImageFormat,
+        codecs::{gif::GifDecoder, png::PngDecoder},
+        metadata::LoopCount,
+    },
+    std::{
+        fs::File,
+        io::BufReader,
+        path::{Path, PathBuf},
+    },
+};
+
+/// Appended to the file stem of the image to name its poster.
+const POSTER_SUFFIX: &str = "-poster";
+
+/// A single frame of an animation.
+#[derive(Clone, Debug)]
+pub struct AnimationFrame {
+    /// The full canvas at the time of the frame.
+    pub image: DynamicImage,
+    /// The time the frame is shown in milliseconds.
+    pub duration: u32,
+}
+
+/// The decoded frames of an animated image.
+#[derive(Clone)]
+pub struct Animation {
+    /// The frames in the order they are shown.
+    pub frames: Vec<AnimationFrame>,
+    /// The amount of times the animation is played.
+    pub loop_count: LoopCount,
+}
+
+impl Animation {
+    /// Decodes all frames of the given gif or png image. Returns `None` if
+    /// the image is not animated, i.e. it has a single frame only.
+    pub fn from(image_file_name: &PathBuf) -> Result<Option<Self>, String> {
+        Self::decode(image_file_name, usize::MAX)
+    }
+
+    /// Returns true if the given image is an animated gif or APNG. At most
+    /// two frames are decoded.
+    pub fn is_animated(image_file_name: &PathBuf) -> Result<bool, String> {
+        Ok(Self::decode(image_file_name, 2)?.is_some())
+    }
+
+    /// Decodes up to `max_frames` frames of the given image.
+    fn decode(
+        image_file_name: &PathBuf,
+        max_frames: usize,
+    ) -> Result<Option<Self>, String> {
+        let format = match ImageFormat::from_path(image_file_name) {
+            Ok(f) => f,
+            Err(_) => return Ok(None),
+        };
+        if format != ImageFormat::Gif && format != ImageFormat::Png {
+            return Ok(None);
+        }
+        let reader = match File::open(image_file_name) {
+            Ok(f) => BufReader::new(f),
+            Err(msg) => return Err(msg.to_string()),
+        };
+        let (frames, loop_count) = match format {
+            ImageFormat::Gif => match GifDecoder::new(reader) {
+                Ok(decoder) => get_frames(decoder),
+                Err(msg) => return Err(msg.to_string()),
+            },
+            _ => {
+                let decoder = match PngDecoder::new(reader) {
+                    Ok(d) => d,
+                    Err(msg) => return Err(msg.to_string()),
+                };
+                match decoder.is_apng() {
+                    Ok(true) => (),
+                    Ok(false) => return Ok(None),
+                    Err(msg) => return Err(msg.to_string()),
+                }
+                match decoder.apng() {
+                    Ok(decoder) => get_frames(decoder),
+                    Err(msg) => return Err(msg.to_string()),
+                }
+            }
+        };
+        let mut animation_frames = vec![];
+        for frame in frames.take(max_frames) {
+            let frame = match frame {
+                Ok(f) => f,
+                Err(msg) => return Err(msg.to_string()),
+            };
+            let (numerator, denominator) = frame.delay().numer_denom_ms();
+            animation_frames.push(AnimationFrame {
+                duration: (numerator as f64 / denominator.max(1) as f64).round()
+                    as u32,
+                image: DynamicImage::ImageRgba8(frame.into_buffer()),
+            });
+        }
+        match animation_frames.len() > 1 {
+            true => Ok(Some(Self {
+                frames: animation_frames,
+                loop_count,
+            })),
+            false => Ok(None),
+        }
+    }
+
+    /// The width of the frames.
+    pub fn width(&self) -> u32 {
+        self.frames.first().map(|f| f.image.width()).unwrap_or(0)
+    }
+
+    /// The height of the frames.
+    pub fn height(&self) -> u32 {
+        self.frames.first().map(|f| f.image.height()).unwrap_or(0)
+    }
+
+    /// The time the animation takes to play once in milliseconds.
+    pub fn get_total_duration(&self) -> u32 {
+        self.frames.iter().map(|f| f.duration).sum()
+    }
+
+    /// Replaces the image of every frame by the result of the given function,
+    /// e.g. a color space conversion.
+    pub fn map_images(
+        self,
+        mut f: impl FnMut(DynamicImage) -> Result<DynamicImage, String>,
+    ) -> Result<Self, String> {
+        let mut frames = vec![];
+        for frame in self.frames {
+            frames.push(AnimationFrame {
+                image: f(frame.image)?,
+                duration: frame.duration,
+            });
+        }
+        Ok(Self {
+            frames,
+            loop_count: self.loop_count,
+        })
+    }
+
+    /// Crops every frame to the given rectangle, if any, and resizes it to
+    /// the given size. The timing is kept.
+    pub fn crop_and_resize(
+        &self,
+        rect: Option<&CropRect>,
+        width: u32,
+        height: u32,
+        params: &ResizeParameter,
+    ) -> Self {
+        let frames = self
+            .frames
+            .iter()
+            .map(|frame| {
+                let prepared = PreparedImage::new(&frame.image, params);
+                let resized = match rect {
+                    Some(rect) => prepared.crop(rect).resize(width, height),
+                    None => prepared.resize(width, height),
+                };
+                AnimationFrame {
+                    image: resized.to_image(),
+                    duration: frame.duration,
+                }
+            })
+            .collect();
+        Self {
+            frames,
+            loop_count: self.loop_count,
+        }
+    }
+}
+
+/// Returns the frames and the loop count of the given decoder.
+fn get_frames<'a>(
+    decoder: impl AnimationDecoder<'a>,
+) -> (Frames<'a>, LoopCount) {
+    let loop_count = decoder.loop_count();
+    (decoder.into_frames(), loop_count)
+}
+
+/// Returns the file name of the static poster of the given animated image,
+/// e.g. `banner-poster.png` for `banner.gif`.
+/// ## Example
+///
+/// ```
+/// use {
+///     html5_picture::utils::animation::get_poster_file_name,
+///     std::path::PathBuf,
+/// };
+///
+/// let poster = get_poster_file_name(&PathBuf::from("assets/banner.gif"));
+/// assert_eq!(poster.unwrap(), PathBuf::from("banner-poster.png"));
+/// ```
+pub fn get_poster_file_name(image_file_name: &Path) -> Result<PathBuf, String> {
+    match image_file_name.file_stem().and_then(|s| s.to_str()) {
+        Some(stem) => {
+            Ok(PathBuf::from(format!("{}{}.png", stem, POSTER_SUFFIX)))
+        }
+        None => Err("File name could not be extracted!".to_string()),
+    }
+}
+
+/// Returns true if the given file is the poster of an animated gif or APNG
+/// next to it. Posters are generated, so they are no source images.
+/// ## Example
+///
+/// ```
+/// use {html5_picture::utils::animation::is_poster, std::path::Path};
+///
+/// // there is no animated banner.gif or banner.png next to it
+/// assert!(!is_poster(Path::new("assets/banner-poster.png")));
+/// assert!(!is_poster(Path::new("assets/banner.png")));
+/// ```
+pub fn is_poster(file_name: &Path) -> bool {
+    let suffix = format!("{}.png", POSTER_SUFFIX);
+    let stem = match file_name
+        .file_name()
+        .and_then(|f| f.to_str())
+        .and_then(|f| f.strip_suffix(&suffix))
+    {
+        Some(s) => s,
+        None => return false,
+    };
+    ["gif", "png"].iter().any(|extension| {
+        let image_file_name =
+            file_name.with_file_name(format!("{}.{}", stem, extension));
+        image_file_name.is_file()
+            && Animation::is_animated(&image_file_name).unwrap_or(false)
+    })
+}
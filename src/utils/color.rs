@@ -0,0 +1,187 @@
+//! Color management of the source images. Images with an embedded ICC
+//! profile, e.g. Adobe RGB or Display P3, are converted to the target color
+//! space, so browsers that assume sRGB do not show them washed out. Images
+//! without profile are assumed to be sRGB already.
+
+use {
+    clap::ArgEnum,
+    image::{DynamicImage, ImageBuffer},
+    moxcms::{ColorProfile, DataColorSpace, Layout, TransformOptions},
+    serde::{Deserialize, Serialize},
+};
+
+/// The color space of the processed images.
+#[derive(ArgEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ColorSpace {
+    /// The color space of the web, supported by every browser.
+    Srgb,
+    /// The wide gamut color space of recent displays. Images in it always
+    /// carry their profile.
+    DisplayP3,
+}
+
+impl ColorSpace {
+    /// Returns the ICC profile of the color space.
+    pub fn get_icc_profile(&self) -> Result<Vec<u8>, String> {
+        match self.get_profile().encode() {
+            Ok(p) => Ok(p),
+            Err(msg) => Err(msg.to_string()),
+        }
+    }
+
+    /// Returns the moxcms profile of the color space.
+    fn get_profile(&self) -> ColorProfile {
+        match self {
+            Self::Srgb => ColorProfile::new_srgb(),
+            Self::DisplayP3 => ColorProfile::new_display_p3(),
+        }
+    }
+}
+
+/// The parameter of the color management.
+#[derive(Clone, Copy, Debug)]
+pub struct ColorParameter {
+    /// The color space images with an embedded profile are converted to.
+    pub color_space: ColorSpace,
+    /// If true, the processed images carry the ICC profile of their color
+    /// space even if it is sRGB.
+    pub embed_icc_profile: bool,
+}
+
+impl Default for ColorParameter {
+    fn default() -> Self {
+        Self {
+            color_space: ColorSpace::Srgb,
+            embed_icc_profile: false,
+        }
+    }
+}
+
+impl ColorParameter {
+    /// Returns the color space of the processed image, depending on whether
+    /// the source image has an embedded profile.
+    pub fn get_color_space(&self, has_icc_profile: bool) -> ColorSpace {
+        match has_icc_profile {
+            true => self.color_space,
+            false => ColorSpace::Srgb,
+        }
+    }
+
+    /// Returns the ICC profile the processed image needs to carry, if any.
+    pub fn get_output_icc_profile(
+        &self,
+        has_icc_profile: bool,
+    ) -> Result<Option<Vec<u8>>, String> {
+        let color_space = self.get_color_space(has_icc_profile);
+        match self.embed_icc_profile || color_space != ColorSpace::Srgb {
+            true => color_space.get_icc_profile().map(Some),
+            false => Ok(None),
+        }
+    }
+}
+
+/// Converts the image from the given ICC profile to the color space. RGB
+/// images with 8 or 16 bit per channel are converted, other images and
+/// profiles of other color spaces are returned unchanged.
+/// ## Example
+///
+/// ```
+/// use html5_picture::utils::color::{ColorSpace, convert};
+///
+/// let img = image::DynamicImage::new_rgb8(4, 4);
+/// let profile = ColorSpace::DisplayP3.get_icc_profile().unwrap();
+/// let converted = convert(img, &profile, ColorSpace::Srgb).unwrap();
+/// assert_eq!(converted.width(), 4);
+/// ```
+pub fn convert(
+    img: DynamicImage,
+    icc_profile: &[u8],
+    color_space: ColorSpace,
+) -> Result<DynamicImage, String> {
+    let source = match ColorProfile::new_from_slice(icc_profile) {
+        Ok(p) => p,
+        Err(msg) => return Err(format!("Invalid ICC profile: {}", msg)),
+    };
+    if source.color_space != DataColorSpace::Rgb {
+        return Ok(img);
+    }
+    let target = color_space.get_profile();
+    // unwraps allowed, the converted buffers have the size of the source
+    match img {
+        DynamicImage::ImageRgb8(buffer) => {
+            let raw = transform_8bit(&source, &target, Layout::Rgb, &buffer)?;
+            let (width, height) = buffer.dimensions();
+            Ok(DynamicImage::ImageRgb8(
+                ImageBuffer::from_raw(width, height, raw).unwrap(),
+            ))
+        }
+        DynamicImage::ImageRgba8(buffer) => {
+            let raw = transform_8bit(&source, &target, Layout::Rgba, &buffer)?;
+            let (width, height) = buffer.dimensions();
+            Ok(DynamicImage::ImageRgba8(
+                ImageBuffer::from_raw(width, height, raw).unwrap(),
+            ))
+        }
+        DynamicImage::ImageRgb16(buffer) => {
+            let raw = transform_16bit(&source, &target, Layout::Rgb, &buffer)?;
+            let (width, height) = buffer.dimensions();
+            Ok(DynamicImage::ImageRgb16(
+                ImageBuffer::from_raw(width, height, raw).unwrap(),
+            ))
+        }
+        DynamicImage::ImageRgba16(buffer) => {
+            let raw = transform_16bit(&source, &target, Layout::Rgba, &buffer)?;
+            let (width, height) = buffer.dimensions();
+            Ok(DynamicImage::ImageRgba16(
+                ImageBuffer::from_raw(width, height, raw).unwrap(),
+            ))
+        }
+        img => Ok(img),
+    }
+}
+
+/// Transforms the 8 bit pixels of the given layout between the profiles.
+fn transform_8bit(
+    source: &ColorProfile,
+    target: &ColorProfile,
+    layout: Layout,
+    pixels: &[u8],
+) -> Result<Vec<u8>, String> {
+    let transform = match source.create_transform_8bit(
+        layout,
+        target,
+        layout,
+        TransformOptions::default(),
+    ) {
+        Ok(t) => t,
+        Err(msg) => return Err(msg.to_string()),
+    };
+    let mut converted = vec![0; pixels.len()];
+    match transform.transform(pixels, &mut converted) {
+        Ok(_) => Ok(converted),
+        Err(msg) => Err(msg.to_string()),
+    }
+}
+
+/// Transforms the 16 bit pixels of the given layout between the profiles.
+fn transform_16bit(
+    source: &ColorProfile,
+    target: &ColorProfile,
+    layout: Layout,
+    pixels: &[u16],
+) -> Result<Vec<u16>, String> {
+    let transform = match source.create_transform_16bit(
+        layout,
+        target,
+        layout,
+        TransformOptions::default(),
+    ) {
+        Ok(t) => t,
+        Err(msg) => return Err(msg.to_string()),
+    };
+    let mut converted = vec![0; pixels.len()];
+    match transform.transform(pixels, &mut converted) {
+        Ok(_) => Ok(converted),
+        Err(msg) => Err(msg.to_string()),
+    }
+}
@@ -0,0 +1,219 @@
+use {
+    serde::{Deserialize, Serialize},
+    std::{fmt, str::FromStr},
+};
+
+/// An aspect ratio like `16:9`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct AspectRatio {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl AspectRatio {
+    /// Calculates the height for the given width.
+    pub fn get_height(&self, width: u32) -> u32 {
+        let height = width as f64 * self.height as f64 / self.width as f64;
+        (height.round() as u32).max(1)
+    }
+}
+
+impl FromStr for AspectRatio {
+    type Err = String;
+
+    /// Parses an aspect ratio given as `width:height` or `width/height`.
+    /// ## Example
+    ///
+    /// ```
+    /// use html5_picture::utils::crop::AspectRatio;
+    ///
+    /// let ratio: AspectRatio = "16:9".parse().unwrap();
+    /// assert_eq!(ratio.width, 16);
+    /// assert_eq!(ratio.height, 9);
+    /// assert_eq!(ratio.get_height(1280), 720);
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (width, height) = match s.split_once([':', '/']) {
+            Some(v) => v,
+            None => {
+                return Err(format!("Invalid aspect ratio {}!", s));
+            }
+        };
+        let width = width.trim().parse::<u32>();
+        let height = height.trim().parse::<u32>();
+        match (width, height) {
+            (Ok(width), Ok(height)) if width > 0 && height > 0 => {
+                Ok(Self { width, height })
+            }
+            _ => Err(format!("Invalid aspect ratio {}!", s)),
+        }
+    }
+}
+
+impl TryFrom<String> for AspectRatio {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<AspectRatio> for String {
+    fn from(value: AspectRatio) -> Self {
+        value.to_string()
+    }
+}
+
+impl fmt::Display for AspectRatio {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.width, self.height)
+    }
+}
+
+/// A rectangle in pixels of the source image.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct CropRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A point of interest in the image, given relative to the image size. The
+/// values range from `0.0` (left/top) to `1.0` (right/bottom).
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct FocalPoint {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl FocalPoint {
+    /// Calculates the position of a crop of the given size that has the
+    /// focal point as center as close as possible.
+    /// ## Example
+    ///
+    /// ```
+    /// use html5_picture::utils::crop::FocalPoint;
+    ///
+    /// let focal_point = FocalPoint { x: 0.9, y: 0.5 };
+    /// assert_eq!(focal_point.get_crop_position(400, 200, 200, 200), (200, 0));
+    /// ```
+    pub fn get_crop_position(
+        &self,
+        image_width: u32,
+        image_height: u32,
+        crop_width: u32,
+        crop_height: u32,
+    ) -> (u32, u32) {
+        let get_offset = |relative: f32, size: u32, crop_size: u32| {
+            let center = relative.clamp(0.0, 1.0) as f64 * size as f64;
+            let offset = (center - crop_size as f64 / 2.0).round().max(0.0);
+            (offset as u32).min(size.saturating_sub(crop_size))
+        };
+        (
+            get_offset(self.x, image_width, crop_width),
+            get_offset(self.y, image_height, crop_height),
+        )
+    }
+}
+
+/// Defines which part of the source image is used.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Crop {
+    /// The biggest area having the given aspect ratio. Its position is
+    /// determined by the focal point of the image or by its content.
+    AspectRatio(AspectRatio),
+    /// A fixed rectangle.
+    Rect(CropRect),
+}
+
+impl Crop {
+    /// Calculates the rectangle of the crop for an image of the given size.
+    /// Crops that only define an aspect ratio are centered.
+    /// ## Example
+    ///
+    /// ```
+    /// use html5_picture::utils::crop::{Crop, CropRect};
+    ///
+    /// let crop = Crop::AspectRatio("1:1".parse().unwrap());
+    /// assert_eq!(
+    ///     crop.get_rect(400, 200),
+    ///     CropRect { x: 100, y: 0, width: 200, height: 200 }
+    /// );
+    /// ```
+    pub fn get_rect(&self, image_width: u32, image_height: u32) -> CropRect {
+        match self {
+            Self::AspectRatio(ratio) => {
+                let (width, height) =
+                    get_crop_size(ratio, image_width, image_height);
+                CropRect {
+                    x: (image_width - width) / 2,
+                    y: (image_height - height) / 2,
+                    width,
+                    height,
+                }
+            }
+            Self::Rect(rect) => {
+                let x = rect.x.min(image_width.saturating_sub(1));
+                let y = rect.y.min(image_height.saturating_sub(1));
+                CropRect {
+                    x,
+                    y,
+                    width: rect.width.clamp(1, image_width - x),
+                    height: rect.height.clamp(1, image_height - y),
+                }
+            }
+        }
+    }
+
+    /// Calculates the rectangle of the crop for an image of the given size,
+    /// placing crops that only define an aspect ratio around the focal point.
+    pub fn get_rect_with_focal_point(
+        &self,
+        image_width: u32,
+        image_height: u32,
+        focal_point: &FocalPoint,
+    ) -> CropRect {
+        match self {
+            Self::AspectRatio(_) => {
+                let (width, height) = self.get_size(image_width, image_height);
+                let (x, y) = focal_point.get_crop_position(
+                    image_width,
+                    image_height,
+                    width,
+                    height,
+                );
+                CropRect {
+                    x,
+                    y,
+                    width,
+                    height,
+                }
+            }
+            Self::Rect(_) => self.get_rect(image_width, image_height),
+        }
+    }
+
+    /// Returns the size of the crop for an image of the given size.
+    pub fn get_size(&self, image_width: u32, image_height: u32) -> (u32, u32) {
+        let rect = self.get_rect(image_width, image_height);
+        (rect.width, rect.height)
+    }
+}
+
+/// Calculates the biggest size having the given aspect ratio that fits into
+/// the image.
+fn get_crop_size(
+    ratio: &AspectRatio,
+    image_width: u32,
+    image_height: u32,
+) -> (u32, u32) {
+    let height = ratio.get_height(image_width);
+    if height <= image_height {
+        return (image_width, height);
+    }
+    let width = image_height as f64 * ratio.width as f64 / ratio.height as f64;
+    ((width.round() as u32).clamp(1, image_width), image_height)
+}
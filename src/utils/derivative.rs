@@ -0,0 +1,141 @@
+use {
+    crate::utils::crop::AspectRatio,
+    serde::{Deserialize, Serialize},
+    std::{
+        path::{Path, PathBuf},
+        str::FromStr,
+    },
+};
+
+/// A named set of images having a fixed aspect ratio, for example square
+/// thumbnails or 16:9 cards. Each derivative is cropped and resized to all
+/// of its widths.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Derivative {
+    /// The name that is used as suffix for the file names.
+    pub name: String,
+    /// The aspect ratio of the images.
+    pub aspect_ratio: AspectRatio,
+    /// The widths of the images in ascending order.
+    pub widths: Vec<u32>,
+}
+
+impl Derivative {
+    /// Constructs the file name of the image having the given width.
+    /// ## Example
+    ///
+    /// ```
+    /// use {
+    ///     html5_picture::utils::derivative::Derivative,
+    ///     std::path::PathBuf,
+    /// };
+    ///
+    /// let derivative: Derivative = "thumb: 1:1 @ 200,400".parse().unwrap();
+    /// let image = PathBuf::from("assets/photo.png");
+    /// assert_eq!(
+    ///     derivative.get_file_name(&image, 200).unwrap().to_str().unwrap(),
+    ///     "photo-thumb-w200.webp"
+    /// );
+    /// ```
+    pub fn get_file_name(
+        &self,
+        image_file_name: &Path,
+        width: u32,
+    ) -> Result<PathBuf, String> {
+        let file_name = get_file_stem(image_file_name)?;
+        Ok(PathBuf::from(format!(
+            "{}-{}-w{}.webp",
+            file_name, self.name, width
+        )))
+    }
+
+    /// Constructs the name of the picture tag file, located in the same
+    /// folder as the given image.
+    pub fn get_tag_file_name(
+        &self,
+        image_file_name: &Path,
+    ) -> Result<PathBuf, String> {
+        let file_name = get_file_stem(image_file_name)?;
+        Ok(image_file_name
+            .with_file_name(format!("{}-{}.html", file_name, self.name)))
+    }
+
+    /// Constructs the common part of the output file names, which is the
+    /// path of the given image without extension, suffixed by the name.
+    /// ## Example
+    ///
+    /// ```
+    /// use {
+    ///     html5_picture::utils::derivative::Derivative,
+    ///     std::path::PathBuf,
+    /// };
+    ///
+    /// let derivative: Derivative = "thumb: 1:1 @ 200".parse().unwrap();
+    /// let image = PathBuf::from("assets/photo.png");
+    /// assert_eq!(
+    ///     derivative.get_output_stem(&image).unwrap(),
+    ///     PathBuf::from("assets/photo-thumb")
+    /// );
+    /// ```
+    pub fn get_output_stem(
+        &self,
+        image_file_name: &Path,
+    ) -> Result<PathBuf, String> {
+        let file_name = get_file_stem(image_file_name)?;
+        Ok(image_file_name
+            .with_file_name(format!("{}-{}", file_name, self.name)))
+    }
+}
+
+impl FromStr for Derivative {
+    type Err = String;
+
+    /// Parses a derivative given as `name: ratio @ widths`, for example
+    /// `thumb: 1:1 @ 200,400` or `card=16:9@640,1280`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "Invalid derivative {}! Expected format: name: ratio @ widths",
+                s
+            )
+        };
+        let (name, rest) = match s.split_once('=') {
+            Some(v) => v,
+            None => s.split_once(':').ok_or_else(invalid)?,
+        };
+        let name = name.trim();
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(invalid());
+        }
+        let (aspect_ratio, widths) =
+            rest.split_once('@').ok_or_else(invalid)?;
+        let aspect_ratio = aspect_ratio.parse::<AspectRatio>()?;
+        let mut widths = widths
+            .split(',')
+            .map(|w| w.trim().parse::<u32>())
+            .collect::<Result<Vec<u32>, _>>()
+            .map_err(|_| invalid())?;
+        widths.sort_unstable();
+        widths.dedup();
+        if widths.is_empty() || widths[0] == 0 {
+            return Err(invalid());
+        }
+        Ok(Self {
+            name: name.to_string(),
+            aspect_ratio,
+            widths,
+        })
+    }
+}
+
+/// Returns the utf-8 checked file stem of the given image.
+fn get_file_stem(image_file_name: &Path) -> Result<String, String> {
+    match image_file_name.file_stem().and_then(|f| f.to_str()) {
+        Some(f) => Ok(f.to_string()),
+        None => Err("No valid filename given!".to_string()),
+    }
+}
@@ -0,0 +1,204 @@
+use {
+    crate::utils::svg::{DEFAULT_SVG_WIDTH, Svg},
+    image::{
+        AnimationDecoder, ExtendedColorType, ImageDecoder, ImageFormat,
+        ImageReader,
+        codecs::{gif::GifDecoder, png::PngDecoder},
+        metadata::Orientation,
+    },
+    std::path::Path,
+};
+
+/// Information about a source image, read once from the header of the file
+/// and shared by the planning of the resized images, the processing and the
+/// tag generation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageInfo {
+    /// The width of the displayed image, after the orientation is applied.
+    pub width: u32,
+    /// The height of the displayed image, after the orientation is applied.
+    pub height: u32,
+    /// The color type as stored in the file.
+    pub color_type: ExtendedColorType,
+    /// True if the image has an alpha channel.
+    pub has_alpha: bool,
+    /// Bits per channel, e.g. 8 or 16.
+    pub bit_depth: u8,
+    /// The orientation given by the EXIF metadata.
+    pub orientation: Orientation,
+    /// The embedded ICC color profile.
+    pub icc_profile: Option<Vec<u8>>,
+    /// The raw EXIF metadata, starting with the TIFF header.
+    pub exif: Option<Vec<u8>>,
+    /// The embedded XMP packet.
+    pub xmp: Option<Vec<u8>>,
+    /// True if the image is an animated gif or APNG. It is only determined
+    /// by `ImageInfo::from`, as `ImageDecoder` does not tell.
+    pub animated: bool,
+}
+
+impl ImageInfo {
+    /// Reads the information from the header of the given image. The pixels
+    /// are not decoded, except for the first two frames of a gif or APNG to
+    /// tell if it is animated. SVGs without intrinsic size are assumed to be
+    /// `DEFAULT_SVG_WIDTH` wide.
+    pub fn from(image_file_name: &Path) -> Result<Self, String> {
+        Self::with_svg_width(image_file_name, DEFAULT_SVG_WIDTH)
+    }
+
+    /// Reads the information like `ImageInfo::from`, SVGs without intrinsic
+    /// size are assumed to be `svg_width` wide.
+    pub fn with_svg_width(
+        image_file_name: &Path,
+        svg_width: u32,
+    ) -> Result<Self, String> {
+        if crate::is_svg(image_file_name) {
+            let (width, height) = Svg::get_size(image_file_name, svg_width)?;
+            return Ok(Self::from_svg_size(width, height));
+        }
+        let reader = match ImageReader::open(image_file_name) {
+            Ok(r) => r,
+            Err(msg) => return Err(msg.to_string()),
+        };
+        // gifs and APNGs are read with their own decoder, that tells if
+        // they are animated without opening the file again
+        match reader.format() {
+            Some(ImageFormat::Gif) => {
+                let mut decoder = match GifDecoder::new(reader.into_inner()) {
+                    Ok(d) => d,
+                    Err(msg) => return Err(msg.to_string()),
+                };
+                let mut info = Self::from_decoder(&mut decoder)?;
+                info.animated = has_multiple_frames(decoder)?;
+                Ok(info)
+            }
+            Some(ImageFormat::Png) => {
+                let mut decoder = match PngDecoder::new(reader.into_inner()) {
+                    Ok(d) => d,
+                    Err(msg) => return Err(msg.to_string()),
+                };
+                let mut info = Self::from_decoder(&mut decoder)?;
+                info.animated = match decoder.is_apng() {
+                    Ok(false) => false,
+                    Ok(true) => match decoder.apng() {
+                        Ok(decoder) => has_multiple_frames(decoder)?,
+                        Err(msg) => return Err(msg.to_string()),
+                    },
+                    Err(msg) => return Err(msg.to_string()),
+                };
+                Ok(info)
+            }
+            _ => match reader.into_decoder() {
+                Ok(mut decoder) => Self::from_decoder(&mut decoder),
+                Err(msg) => Err(msg.to_string()),
+            },
+        }
+    }
+
+    /// Reads the information from the given decoder. The decoder can be used
+    /// to decode the image afterwards, so the file is only opened once.
+    /// ## Example
+    ///
+    /// ```
+    /// use {
+    ///     html5_picture::utils::ImageInfo,
+    ///     image::{DynamicImage, ImageFormat, codecs::png::PngDecoder},
+    ///     std::io::Cursor,
+    /// };
+    ///
+    /// let mut png = Cursor::new(vec![]);
+    /// DynamicImage::new_rgba16(40, 20)
+    ///     .write_to(&mut png, ImageFormat::Png)
+    ///     .unwrap();
+    /// png.set_position(0);
+    /// let mut decoder = PngDecoder::new(png).unwrap();
+    /// let info = ImageInfo::from_decoder(&mut decoder).unwrap();
+    /// assert_eq!((info.width, info.height, info.bit_depth), (40, 20, 16));
+    /// assert!(info.has_alpha && info.icc_profile.is_none());
+    /// let img = DynamicImage::from_decoder(decoder).unwrap();
+    /// assert_eq!(img.width(), 40);
+    /// ```
+    pub fn from_decoder(
+        decoder: &mut impl ImageDecoder,
+    ) -> Result<Self, String> {
+        let (width, height) = decoder.dimensions();
+        let color_type = decoder.original_color_type();
+        let channel_count = color_type.channel_count().max(1);
+        let icc_profile = match decoder.icc_profile() {
+            Ok(p) => p,
+            Err(msg) => return Err(msg.to_string()),
+        };
+        let exif = match decoder.exif_metadata() {
+            Ok(e) => e,
+            Err(msg) => return Err(msg.to_string()),
+        };
+        let xmp = match decoder.xmp_metadata() {
+            Ok(x) => x,
+            Err(msg) => return Err(msg.to_string()),
+        };
+        let orientation = match decoder.orientation() {
+            Ok(o) => o,
+            Err(msg) => return Err(msg.to_string()),
+        };
+        // rotated images are displayed with swapped dimensions
+        let (width, height) = match orientation {
+            Orientation::Rotate90
+            | Orientation::Rotate270
+            | Orientation::Rotate90FlipH
+            | Orientation::Rotate270FlipH => (height, width),
+            _ => (width, height),
+        };
+        Ok(Self {
+            width,
+            height,
+            color_type,
+            has_alpha: decoder.color_type().has_alpha(),
+            bit_depth: (color_type.bits_per_pixel() / channel_count as u16)
+                as u8,
+            orientation,
+            icc_profile,
+            exif,
+            xmp,
+            animated: false,
+        })
+    }
+
+    /// Creates the information of the given SVG, as it is rasterized.
+    pub fn from_svg(svg: &Svg) -> Self {
+        Self::from_svg_size(svg.width(), svg.height())
+    }
+
+    /// Creates the information of a SVG of the given displayed size.
+    fn from_svg_size(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            color_type: ExtendedColorType::Rgba8,
+            has_alpha: true,
+            bit_depth: 8,
+            orientation: Orientation::NoTransforms,
+            icc_profile: None,
+            exif: None,
+            xmp: None,
+            animated: false,
+        }
+    }
+
+    /// Calculates the height of the image scaled to the given width,
+    /// preserving the aspect ratio.
+    pub fn get_height(&self, width: u32) -> u32 {
+        let scale_factor = width as f64 / self.width as f64;
+        (scale_factor * self.height as f64) as u32
+    }
+}
+
+/// Returns true if the given decoder yields more than one frame. At most two
+/// frames are decoded, like `Animation::is_animated` does.
+fn has_multiple_frames<'a>(
+    decoder: impl AnimationDecoder<'a>,
+) -> Result<bool, String> {
+    match decoder.into_frames().take(2).collect::<Result<Vec<_>, _>>() {
+        Ok(frames) => Ok(frames.len() > 1),
+        Err(msg) => Err(msg.to_string()),
+    }
+}
@@ -0,0 +1,109 @@
+use {
+    crate::utils::Placeholder,
+    serde::{Deserialize, Serialize},
+    std::path::{Path, PathBuf},
+};
+
+/// Suffix of the metadata file of an image.
+const METADATA_FILE_SUFFIX: &str = "html5picture.json";
+
+/// Contains details of an image that are determined during processing and
+/// that are required for the creation of the `<picture>` tag afterwards.
+/// It is stored as JSON file in the metadata directory, see
+/// `crate::path::get_metadata_dir`, so it is not installed with the images.
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct ImageMetadata {
+    /// The low quality image placeholder, if generated.
+    #[serde(default)]
+    pub placeholder: Option<Placeholder>,
+    /// The dominant color of the image as CSS hex string.
+    #[serde(default)]
+    pub dominant_color: Option<String>,
+    /// The color palette of the image as CSS hex strings, ordered by the
+    /// amount of pixels they represent.
+    #[serde(default)]
+    pub palette: Vec<String>,
+}
+
+impl ImageMetadata {
+    /// Loads the metadata that belongs to the given image, which is located
+    /// in the metadata directory. Returns the default metadata if no file
+    /// exists.
+    pub fn from(image_file_name: &Path) -> Result<Self, String> {
+        let file_name = Self::get_file_name(image_file_name)?;
+        if !file_name.is_file() {
+            return Ok(Self::default());
+        }
+        let content = match std::fs::read_to_string(&file_name) {
+            Ok(c) => c,
+            Err(msg) => return Err(msg.to_string()),
+        };
+        match serde_json::from_str(&content) {
+            Ok(v) => Ok(v),
+            Err(msg) => Err(format!("{}: {}", file_name.display(), msg)),
+        }
+    }
+
+    /// Saves the metadata that belongs to the given image, which is located
+    /// in the metadata directory. Missing folders are created.
+    pub fn save(&self, image_file_name: &Path) -> Result<(), String> {
+        let file_name = Self::get_file_name(image_file_name)?;
+        if let Some(parent) = file_name.parent()
+            && let Err(msg) = std::fs::create_dir_all(parent)
+        {
+            return Err(msg.to_string());
+        }
+        let content = match serde_json::to_string(&self) {
+            Ok(c) => c,
+            Err(msg) => return Err(msg.to_string()),
+        };
+        match std::fs::write(file_name, content) {
+            Ok(_) => Ok(()),
+            Err(msg) => Err(msg.to_string()),
+        }
+    }
+
+    /// Removes the metadata that belongs to the given image, which is located
+    /// in the metadata directory. A missing file is no error.
+    pub fn remove(image_file_name: &Path) -> Result<(), String> {
+        let file_name = Self::get_file_name(image_file_name)?;
+        match std::fs::remove_file(file_name) {
+            Ok(_) => Ok(()),
+            Err(msg) if msg.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(msg) => Err(msg.to_string()),
+        }
+    }
+
+    /// Constructs the file name of the metadata file for the given image.
+    /// ## Example
+    ///
+    /// ```
+    /// use {
+    ///     html5_picture::utils::ImageMetadata,
+    ///     std::path::PathBuf,
+    /// };
+    ///
+    /// let image = PathBuf::from("assets/some/picture.png");
+    /// let metadata = ImageMetadata::get_file_name(&image).unwrap();
+    /// assert_eq!(
+    ///     metadata.to_str().unwrap(),
+    ///     "assets/some/picture.html5picture.json"
+    /// );
+    /// ```
+    pub fn get_file_name(image_file_name: &Path) -> Result<PathBuf, String> {
+        let file_name = match image_file_name.file_stem() {
+            Some(f) => f,
+            None => return Err("No filename given!".to_string()),
+        };
+        let file_name = match file_name.to_str() {
+            Some(f) => f,
+            None => {
+                return Err(
+                    "utf-8 check failed for metadata filename".to_string()
+                );
+            }
+        };
+        Ok(image_file_name
+            .with_file_name(format!("{}.{}", file_name, METADATA_FILE_SUFFIX)))
+    }
+}
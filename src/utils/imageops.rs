@@ -1,15 +1,253 @@
-use std::path::PathBuf;
-
-/// Resizes the image preserving the aspect ratio. Returns the new height.
-pub fn calculate_height_preserve_aspect_ratio(
-    image_file_name: &PathBuf,
-    width: u32,
-) -> Result<u32, String> {
-    // get image dimensions
-    let (w, h) = match image::image_dimensions(&image_file_name) {
-        Err(msg) => return Err(msg.to_string()),
-        Ok((w, h)) => (w, h),
+use {
+    crate::utils::crop::{Crop, CropRect, FocalPoint},
+    image::{DynamicImage, ImageBuffer},
+    std::borrow::Cow,
+};
+
+/// Maximum width and height of the image the saliency is calculated on.
+const SMART_CROP_SAMPLE_SIZE: u32 = 256;
+
+/// The 4x4 Bayer matrix used to dither images to 8 bit per channel.
+const DITHER_MATRIX: [u8; 16] =
+    [0, 8, 2, 10, 12, 4, 14, 6, 3, 11, 1, 9, 15, 7, 13, 5];
+
+/// Determines the rectangle of the given crop in the image. Crops that only
+/// define an aspect ratio are placed around the focal point if given,
+/// otherwise the most salient area of the image is used.
+pub fn get_crop_rect(
+    img: &DynamicImage,
+    crop: &Crop,
+    focal_point: Option<&FocalPoint>,
+) -> CropRect {
+    let (w, h) = (img.width(), img.height());
+    match (crop, focal_point) {
+        (Crop::Rect(_), _) => crop.get_rect(w, h),
+        (Crop::AspectRatio(_), Some(focal_point)) => {
+            crop.get_rect_with_focal_point(w, h, focal_point)
+        }
+        (Crop::AspectRatio(_), None) => {
+            let (width, height) = crop.get_size(w, h);
+            let (x, y) = get_smart_crop_position(img, width, height);
+            CropRect {
+                x,
+                y,
+                width,
+                height,
+            }
+        }
+    }
+}
+
+/// Searches the position of the crop having the given size that contains
+/// the most salient area of the image. The saliency of a pixel is determined
+/// by its edge strength and its saturation. If several positions are equally
+/// good, the one closest to the center is used.
+/// ## Example
+///
+/// ```
+/// use {
+///     html5_picture::utils::imageops::get_smart_crop_position,
+///     image::{DynamicImage, Rgb, RgbImage},
+/// };
+///
+/// // a gray image with a high contrast checkerboard in the top right corner
+/// let img = RgbImage::from_fn(600, 400, |x, y| {
+///     if x >= 480 && y < 120 && (x / 8 + y / 8) % 2 == 0 {
+///         Rgb([0, 0, 0])
+///     } else if x >= 480 && y < 120 {
+///         Rgb([255, 255, 255])
+///     } else {
+///         Rgb([128, 128, 128])
+///     }
+/// });
+/// let (x, y) = get_smart_crop_position(&DynamicImage::ImageRgb8(img), 200, 200);
+/// assert!(x <= 480 && x + 200 >= 600);
+/// assert!(y == 0);
+/// ```
+pub fn get_smart_crop_position(
+    img: &DynamicImage,
+    crop_width: u32,
+    crop_height: u32,
+) -> (u32, u32) {
+    let (w, h) = (img.width(), img.height());
+    if crop_width >= w && crop_height >= h {
+        return (0, 0);
+    }
+    let sample = if w > SMART_CROP_SAMPLE_SIZE || h > SMART_CROP_SAMPLE_SIZE {
+        img.thumbnail(SMART_CROP_SAMPLE_SIZE, SMART_CROP_SAMPLE_SIZE)
+    } else {
+        img.clone()
+    };
+    let sample = sample.to_rgba8();
+    let (sw, sh) = sample.dimensions();
+    let scale_x = sw as f64 / w as f64;
+    let scale_y = sh as f64 / h as f64;
+    let window_width =
+        ((crop_width as f64 * scale_x).round() as u32).clamp(1, sw);
+    let window_height =
+        ((crop_height as f64 * scale_y).round() as u32).clamp(1, sh);
+
+    // summed area table of the saliency, one row and column larger than the
+    // sample to avoid special cases at the borders
+    let luma = |x: u32, y: u32| {
+        let p = sample.get_pixel(x, y);
+        0.299 * p[0] as f64 + 0.587 * p[1] as f64 + 0.114 * p[2] as f64
+    };
+    let stride = (sw + 1) as usize;
+    let mut table = vec![0f64; stride * (sh + 1) as usize];
+    for y in 0..sh {
+        let mut row_sum = 0.0;
+        for x in 0..sw {
+            let p = sample.get_pixel(x, y);
+            let center = luma(x, y);
+            let mut edge = 0.0;
+            for (nx, ny) in [
+                (x.wrapping_sub(1), y),
+                (x + 1, y),
+                (x, y.wrapping_sub(1)),
+                (x, y + 1),
+            ] {
+                if nx < sw && ny < sh {
+                    edge += (center - luma(nx, ny)).abs();
+                }
+            }
+            let max = p[0].max(p[1]).max(p[2]) as f64;
+            let min = p[0].min(p[1]).min(p[2]) as f64;
+            let saturation = max - min;
+            let alpha = p[3] as f64 / 255.0;
+            row_sum += (edge + 0.5 * saturation) * alpha;
+            let idx = (y as usize + 1) * stride + x as usize + 1;
+            table[idx] = table[idx - stride] + row_sum;
+        }
+    }
+    let area_sum = |x: u32, y: u32| {
+        let (x0, y0) = (x as usize, y as usize);
+        let (x1, y1) =
+            (x0 + window_width as usize, y0 + window_height as usize);
+        table[y1 * stride + x1]
+            - table[y0 * stride + x1]
+            - table[y1 * stride + x0]
+            + table[y0 * stride + x0]
     };
-    let scale_factor = width as f64 / w as f64;
-    Ok((scale_factor * h as f64) as u32)
+
+    let center_x = (sw - window_width) as f64 / 2.0;
+    let center_y = (sh - window_height) as f64 / 2.0;
+    let mut best = (0, 0);
+    let mut best_score = f64::MIN;
+    let mut best_distance = f64::MAX;
+    for y in 0..=(sh - window_height) {
+        for x in 0..=(sw - window_width) {
+            let score = area_sum(x, y);
+            let distance =
+                (x as f64 - center_x).powi(2) + (y as f64 - center_y).powi(2);
+            if score > best_score
+                || (score == best_score && distance < best_distance)
+            {
+                best = (x, y);
+                best_score = score;
+                best_distance = distance;
+            }
+        }
+    }
+
+    let x = ((best.0 as f64 / scale_x).round() as u32)
+        .min(w.saturating_sub(crop_width));
+    let y = ((best.1 as f64 / scale_y).round() as u32)
+        .min(h.saturating_sub(crop_height));
+    (x, y)
+}
+
+/// Converts the image to 8 bit RGB or RGBA, the pixel formats supported by
+/// the webp encoder. Grayscale images are expanded to RGB, images with a
+/// higher bit depth are reduced by an ordered dither, so smooth gradients do
+/// not show banding. Float images are clipped to the displayable range.
+/// Palette images are already expanded to RGB by the decoder. Images in a
+/// supported format are returned unchanged.
+/// ## Example
+///
+/// ```
+/// use {
+///     html5_picture::utils::imageops::to_8bit_rgb,
+///     image::{DynamicImage, ImageBuffer, Luma},
+/// };
+///
+/// for img in [
+///     DynamicImage::new_luma8(8, 8),
+///     DynamicImage::new_luma_a8(8, 8),
+///     DynamicImage::new_rgb8(8, 8),
+///     DynamicImage::new_rgba8(8, 8),
+///     DynamicImage::new_luma16(8, 8),
+///     DynamicImage::new_luma_a16(8, 8),
+///     DynamicImage::new_rgb16(8, 8),
+///     DynamicImage::new_rgba16(8, 8),
+///     DynamicImage::new_rgb32f(8, 8),
+///     DynamicImage::new_rgba32f(8, 8),
+/// ] {
+///     let converted = to_8bit_rgb(&img);
+///     assert_eq!(converted.color().has_alpha(), img.color().has_alpha());
+///     assert!(webp::Encoder::from_image(&converted).is_ok());
+/// }
+///
+/// // a 16 bit value halfway between two 8 bit values is dithered to both
+/// let img = DynamicImage::ImageLuma16(ImageBuffer::from_pixel(
+///     4,
+///     4,
+///     Luma([100 * 257 + 128]),
+/// ));
+/// let converted = to_8bit_rgb(&img).to_rgb8();
+/// assert_eq!(converted.pixels().filter(|p| p[0] == 100).count(), 8);
+/// assert_eq!(converted.pixels().filter(|p| p[0] == 101).count(), 8);
+/// ```
+pub fn to_8bit_rgb(img: &DynamicImage) -> Cow<'_, DynamicImage> {
+    match img {
+        DynamicImage::ImageRgb8(_) | DynamicImage::ImageRgba8(_) => {
+            Cow::Borrowed(img)
+        }
+        DynamicImage::ImageLuma8(_) => {
+            Cow::Owned(DynamicImage::ImageRgb8(img.to_rgb8()))
+        }
+        DynamicImage::ImageLumaA8(_) => {
+            Cow::Owned(DynamicImage::ImageRgba8(img.to_rgba8()))
+        }
+        // unwraps allowed, the dithered buffers have the size of the source
+        _ if img.color().has_alpha() => {
+            let img = img.to_rgba16();
+            let (width, height) = img.dimensions();
+            let raw = dither(&img, width, 4);
+            Cow::Owned(DynamicImage::ImageRgba8(
+                ImageBuffer::from_raw(width, height, raw).unwrap(),
+            ))
+        }
+        _ => {
+            let img = img.to_rgb16();
+            let (width, height) = img.dimensions();
+            let raw = dither(&img, width, 3);
+            Cow::Owned(DynamicImage::ImageRgb8(
+                ImageBuffer::from_raw(width, height, raw).unwrap(),
+            ))
+        }
+    }
+}
+
+/// Reduces the given 16 bit samples of an image with the given width and
+/// channel count to 8 bit by an ordered dither.
+fn dither(samples: &[u16], width: u32, channels: usize) -> Vec<u8> {
+    let width = width as usize;
+    samples
+        .iter()
+        .enumerate()
+        .map(|(i, sample)| {
+            let (x, y) = ((i / channels) % width, i / channels / width);
+            let threshold = get_dither_threshold(x, y);
+            (*sample as f32 * 255.0 / 65535.0 + threshold)
+                .round()
+                .clamp(0.0, 255.0) as u8
+        })
+        .collect()
+}
+
+/// Returns the ordered dithering threshold of the given pixel, between -0.5
+/// and 0.5 of an 8 bit step.
+pub(crate) fn get_dither_threshold(x: usize, y: usize) -> f32 {
+    (DITHER_MATRIX[(y % 4) * 4 + x % 4] as f32 + 0.5) / 16.0 - 0.5
 }
@@ -0,0 +1,450 @@
+//! Controls which EXIF and XMP metadata of the source images is kept in the
+//! converted images and the installed originals. GPS coordinates are never
+//! kept, as they reveal where a photo has been taken.
+
+use {
+    crate::utils::xmp,
+    clap::ArgEnum,
+    image::metadata::Orientation,
+    serde::{Deserialize, Serialize},
+    std::ops::Range,
+};
+
+/// Tag of the IFD0 entry containing the orientation.
+const ORIENTATION_TAG: u16 = 0x0112;
+
+/// Tag of the IFD0 entry pointing to the GPS IFD.
+const GPS_IFD_TAG: u16 = 0x8825;
+
+/// Tag of the IFD0 entry containing the author.
+const ARTIST_TAG: u16 = 0x013b;
+
+/// Tag of the IFD0 entry containing the copyright notice.
+const COPYRIGHT_TAG: u16 = 0x8298;
+
+/// The size of an IFD entry in bytes.
+const ENTRY_SIZE: usize = 12;
+
+/// The signature every PNG image starts with.
+const PNG_SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+
+/// Keyword of the PNG text chunk containing the XMP packet.
+const XMP_KEYWORD: &[u8] = b"XML:com.adobe.xmp";
+
+/// Prefix of the keywords of PNG text chunks containing raw EXIF, XMP or
+/// IPTC profiles, as written by ImageMagick.
+const RAW_PROFILE_KEYWORD: &[u8] = b"Raw profile type ";
+
+/// The metadata kept in the converted images.
+#[derive(
+    ArgEnum, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq,
+)]
+pub enum MetadataPolicy {
+    /// Removes all EXIF and XMP metadata.
+    #[default]
+    Strip,
+    /// Keeps the author and the copyright notice only.
+    Copyright,
+    /// Keeps all metadata except the GPS coordinates. The orientation is
+    /// reset, as the pixels are already rotated.
+    KeepExceptGps,
+}
+
+impl MetadataPolicy {
+    /// Returns the EXIF metadata that is kept of the given raw EXIF data.
+    /// Data that cannot be parsed is removed.
+    /// ## Example
+    ///
+    /// ```
+    /// use html5_picture::utils::metadata::MetadataPolicy;
+    ///
+    /// // little endian TIFF with the GPS IFD pointer as only IFD0 entry,
+    /// // followed by an empty GPS IFD
+    /// let mut exif = b"II*\0\x08\0\0\0\x01\0".to_vec();
+    /// exif.extend_from_slice(&[0x25, 0x88, 4, 0, 1, 0, 0, 0, 26, 0, 0, 0]);
+    /// exif.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    /// let kept = MetadataPolicy::KeepExceptGps.filter_exif(&exif).unwrap();
+    /// assert_eq!(&kept[8..10], &[0, 0]);
+    /// assert!(MetadataPolicy::Copyright.filter_exif(&exif).is_none());
+    /// assert!(MetadataPolicy::Strip.filter_exif(&exif).is_none());
+    /// ```
+    pub fn filter_exif(&self, exif: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            Self::Strip => None,
+            Self::Copyright => copy_entries(exif, &[ARTIST_TAG, COPYRIGHT_TAG]),
+            Self::KeepExceptGps => {
+                let mut exif = exif.to_vec();
+                remove_gps_ifd(&mut exif)?;
+                let _ = Orientation::remove_from_exif_chunk(&mut exif);
+                Some(exif)
+            }
+        }
+    }
+
+    /// Returns the XMP packet that is kept of the given one. Packets that
+    /// cannot be parsed are removed.
+    /// ## Example
+    ///
+    /// ```
+    /// use html5_picture::utils::metadata::MetadataPolicy;
+    ///
+    /// let xmp = br#"<x:xmpmeta><rdf:Description exif:GPSLatitude="52,31N"
+    ///     dc:format="image/jpeg"/></x:xmpmeta>"#;
+    /// let kept = MetadataPolicy::KeepExceptGps.filter_xmp(xmp).unwrap();
+    /// let kept = String::from_utf8(kept).unwrap();
+    /// assert!(!kept.contains("GPS") && kept.contains("dc:format"));
+    /// ```
+    pub fn filter_xmp(&self, xmp: &[u8]) -> Option<Vec<u8>> {
+        let xmp = std::str::from_utf8(xmp).ok()?;
+        let xmp = match self {
+            Self::Strip => return None,
+            Self::Copyright => xmp::create_rights_packet(xmp)?,
+            Self::KeepExceptGps => {
+                let xmp = xmp::remove_properties(xmp, "exif:GPS")?;
+                xmp::remove_properties(&xmp, "tiff:Orientation")?
+            }
+        };
+        Some(xmp.into_bytes())
+    }
+
+    /// Returns the EXIF metadata that is kept in the installed original of
+    /// the given raw EXIF data. Unlike `filter_exif`, the orientation is
+    /// kept in any case, as the pixels of the original are not rotated.
+    /// ## Example
+    ///
+    /// ```
+    /// use html5_picture::utils::metadata::MetadataPolicy;
+    ///
+    /// // big endian TIFF with the orientation and the GPS IFD pointer
+    /// let mut exif = b"MM\0*\0\0\0\x08\0\x02".to_vec();
+    /// exif.extend_from_slice(&[1, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0]);
+    /// exif.extend_from_slice(&[0x88, 0x25, 0, 4, 0, 0, 0, 1, 0, 0, 0, 38]);
+    /// exif.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    /// let kept = MetadataPolicy::Strip.filter_original_exif(&exif).unwrap();
+    /// assert_eq!(&kept[..10], b"MM\0*\0\0\0\x08\0\x01");
+    /// assert_eq!(&kept[10..22], &exif[10..22]);
+    /// let kept = MetadataPolicy::KeepExceptGps
+    ///     .filter_original_exif(&exif)
+    ///     .unwrap();
+    /// assert_eq!(&kept[8..10], &[0, 1]);
+    /// assert_eq!(&kept[10..22], &exif[10..22]);
+    /// ```
+    pub fn filter_original_exif(&self, exif: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            Self::Strip => copy_entries(exif, &[ORIENTATION_TAG]),
+            Self::Copyright => copy_entries(
+                exif,
+                &[ORIENTATION_TAG, ARTIST_TAG, COPYRIGHT_TAG],
+            ),
+            Self::KeepExceptGps => {
+                let mut exif = exif.to_vec();
+                remove_gps_ifd(&mut exif)?;
+                Some(exif)
+            }
+        }
+    }
+
+    /// Returns the XMP packet that is kept in the installed original of the
+    /// given one. Unlike `filter_xmp`, the orientation is kept.
+    pub fn filter_original_xmp(&self, xmp: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            Self::KeepExceptGps => {
+                let xmp = std::str::from_utf8(xmp).ok()?;
+                Some(xmp::remove_properties(xmp, "exif:GPS")?.into_bytes())
+            }
+            _ => self.filter_xmp(xmp),
+        }
+    }
+
+    /// Filters the EXIF and XMP metadata of the given PNG image as given by
+    /// `filter_original_exif` and `filter_original_xmp`. All other chunks,
+    /// including the image data, are copied as they are. Text chunks with
+    /// raw profiles are removed, as they may contain GPS coordinates too.
+    /// ## Example
+    ///
+    /// ```
+    /// use {
+    ///     html5_picture::utils::metadata::MetadataPolicy,
+    ///     image::{ImageDecoder, codecs::png::PngDecoder},
+    ///     std::io::Cursor,
+    /// };
+    ///
+    /// let mut png = vec![];
+    /// image::DynamicImage::new_rgb8(2, 2)
+    ///     .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+    ///     .unwrap();
+    /// // inserts an eXIf chunk without orientation before the IEND chunk
+    /// let exif = b"II*\0\x08\0\0\0\0\0\0\0\0\0";
+    /// let mut chunk = (exif.len() as u32).to_be_bytes().to_vec();
+    /// chunk.extend_from_slice(b"eXIf");
+    /// chunk.extend_from_slice(exif);
+    /// chunk.extend_from_slice(&[0; 4]);
+    /// let iend = png.len() - 12;
+    /// png.splice(iend..iend, chunk);
+    ///
+    /// let png = MetadataPolicy::Strip.filter_png(&png).unwrap();
+    /// let mut decoder = PngDecoder::new(Cursor::new(&png)).unwrap();
+    /// assert!(decoder.exif_metadata().unwrap().is_none());
+    /// assert_eq!(decoder.dimensions(), (2, 2));
+    /// ```
+    pub fn filter_png(&self, png: &[u8]) -> Result<Vec<u8>, String> {
+        if !png.starts_with(PNG_SIGNATURE) {
+            return Err("Invalid PNG image!".to_string());
+        }
+        let mut chunks = vec![];
+        let mut exif = None;
+        let mut xmp = None;
+        let mut offset = PNG_SIGNATURE.len();
+        while offset < png.len() {
+            let chunk = match read_png_chunk(png, offset) {
+                Some(c) => c,
+                None => return Err("Truncated PNG chunk!".to_string()),
+            };
+            offset += 12 + chunk.data.len();
+            match &chunk.id {
+                b"eXIf" => exif = Some(chunk.data),
+                b"iTXt" | b"tEXt" | b"zTXt" => {
+                    let keyword = chunk.data.split(|b| *b == 0).next();
+                    match keyword.unwrap_or_default() {
+                        XMP_KEYWORD => xmp = read_xmp_text(&chunk),
+                        k if k.starts_with(RAW_PROFILE_KEYWORD) => (),
+                        _ => chunks.push(chunk),
+                    }
+                }
+                _ => chunks.push(chunk),
+            }
+        }
+        let mut exif = exif.and_then(|exif| self.filter_original_exif(exif));
+        let mut xmp = xmp.and_then(|xmp| self.filter_original_xmp(xmp));
+        let mut filtered = PNG_SIGNATURE.to_vec();
+        for chunk in chunks {
+            // the metadata precedes the image data
+            if &chunk.id == b"IDAT" {
+                if let Some(exif) = exif.take() {
+                    write_png_chunk(&mut filtered, b"eXIf", &exif);
+                }
+                if let Some(xmp) = xmp.take() {
+                    let mut text = XMP_KEYWORD.to_vec();
+                    // no compression, language and translated keyword
+                    text.extend_from_slice(&[0, 0, 0, 0, 0]);
+                    text.extend_from_slice(&xmp);
+                    write_png_chunk(&mut filtered, b"iTXt", &text);
+                }
+            }
+            write_png_chunk(&mut filtered, &chunk.id, chunk.data);
+        }
+        Ok(filtered)
+    }
+}
+
+/// A chunk of a PNG image.
+struct PngChunk<'a> {
+    id: [u8; 4],
+    data: &'a [u8],
+}
+
+/// Reads the PNG chunk at the given offset. The CRC is not checked.
+fn read_png_chunk(png: &[u8], offset: usize) -> Option<PngChunk<'_>> {
+    let size =
+        u32::from_be_bytes(png.get(offset..offset + 4)?.try_into().ok()?);
+    let id = png.get(offset + 4..offset + 8)?.try_into().ok()?;
+    let start = offset + 8;
+    let end = start.checked_add(size as usize)?;
+    // the CRC follows the data
+    png.get(end..end.checked_add(4)?)?;
+    Some(PngChunk {
+        id,
+        data: png.get(start..end)?,
+    })
+}
+
+/// Returns the XMP packet of the given text chunk. Compressed packets are
+/// removed.
+fn read_xmp_text<'a>(chunk: &PngChunk<'a>) -> Option<&'a [u8]> {
+    if &chunk.id != b"iTXt" {
+        return None;
+    }
+    let data = chunk.data.get(XMP_KEYWORD.len() + 1..)?;
+    if data.first() != Some(&0) {
+        return None;
+    }
+    // skips the compression flag and method, the language and the
+    // translated keyword, both terminated by zero
+    let mut parts = data.get(2..)?.splitn(3, |b| *b == 0);
+    parts.next()?;
+    parts.next()?;
+    parts.next()
+}
+
+/// Appends a PNG chunk with the given id and data.
+fn write_png_chunk(png: &mut Vec<u8>, id: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(id);
+    png.extend_from_slice(data);
+    // the CRC covers the id and the data
+    let crc = crc32fast::hash(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Removes the GPS IFD from the given EXIF data. The pointer is removed from
+/// IFD0 and the GPS IFD including its values is overwritten by zeros, so no
+/// coordinates remain in the data. Returns `None` if the data is invalid.
+fn remove_gps_ifd(exif: &mut [u8]) -> Option<()> {
+    let little_endian = read_byte_order(exif)?;
+    let ifd0 = read_u32(exif, 4, little_endian)? as usize;
+    let entry = match find_entry(exif, ifd0, GPS_IFD_TAG, little_endian) {
+        Some(e) => e,
+        None => return Some(()),
+    };
+    let gps_ifd = read_u32(exif, entry + 8, little_endian)? as usize;
+    if let Some(count) = read_u16(exif, gps_ifd, little_endian) {
+        for i in 0..count as usize {
+            let gps_entry = gps_ifd + 2 + i * ENTRY_SIZE;
+            if let Some(value) = get_value_range(exif, gps_entry, little_endian)
+                .and_then(|range| exif.get_mut(range))
+            {
+                value.fill(0);
+            }
+        }
+        let end =
+            (gps_ifd + 2 + count as usize * ENTRY_SIZE + 4).min(exif.len());
+        exif[gps_ifd..end].fill(0);
+    }
+    // the following entries and the offset of the next IFD move up
+    let count = read_u16(exif, ifd0, little_endian)?;
+    let end = ifd0 + 2 + count as usize * ENTRY_SIZE + 4;
+    if end > exif.len() {
+        return None;
+    }
+    exif.copy_within(entry + ENTRY_SIZE..end, entry);
+    exif[end - ENTRY_SIZE..end].fill(0);
+    let count = match little_endian {
+        true => (count - 1).to_le_bytes(),
+        false => (count - 1).to_be_bytes(),
+    };
+    exif[ifd0..ifd0 + 2].copy_from_slice(&count);
+    Some(())
+}
+
+/// Creates EXIF data containing the IFD0 entries with the given tags of the
+/// given EXIF data only, keeping its byte order. The tags are given in
+/// ascending order. Returns `None` if none of them exists.
+fn copy_entries(exif: &[u8], tags: &[u16]) -> Option<Vec<u8>> {
+    let little_endian = read_byte_order(exif)?;
+    let ifd0 = read_u32(exif, 4, little_endian)? as usize;
+    let entries: Vec<(&[u8], &[u8])> = tags
+        .iter()
+        .filter_map(|tag| {
+            let entry = find_entry(exif, ifd0, *tag, little_endian)?;
+            let range = get_value_range(exif, entry, little_endian)?;
+            Some((exif.get(entry..entry + ENTRY_SIZE)?, exif.get(range)?))
+        })
+        .collect();
+    if entries.is_empty() {
+        return None;
+    }
+    // TIFF with a single IFD, the values follow the IFD
+    let mut tiff = exif[0..4].to_vec();
+    write_u32(&mut tiff, 8, little_endian);
+    write_u16(&mut tiff, entries.len() as u16, little_endian);
+    let mut data = vec![];
+    let data_offset = 8 + 2 + entries.len() * ENTRY_SIZE + 4;
+    for (entry, value) in entries {
+        // the tag, type and count are kept as they are
+        tiff.extend_from_slice(&entry[..8]);
+        if value.len() <= 4 {
+            tiff.extend_from_slice(&entry[8..]);
+        } else {
+            let offset = (data_offset + data.len()) as u32;
+            write_u32(&mut tiff, offset, little_endian);
+            data.extend_from_slice(value);
+            if value.len() % 2 == 1 {
+                data.push(0);
+            }
+        }
+    }
+    write_u32(&mut tiff, 0, little_endian);
+    tiff.extend_from_slice(&data);
+    Some(tiff)
+}
+
+/// Reads the byte order from the TIFF header, true if little endian.
+fn read_byte_order(exif: &[u8]) -> Option<bool> {
+    match exif.get(0..4)? {
+        b"II*\0" => Some(true),
+        b"MM\0*" => Some(false),
+        _ => None,
+    }
+}
+
+/// Returns the offset of the entry with the given tag in the IFD at the given
+/// offset.
+fn find_entry(
+    exif: &[u8],
+    ifd: usize,
+    tag: u16,
+    little_endian: bool,
+) -> Option<usize> {
+    let count = read_u16(exif, ifd, little_endian)? as usize;
+    (0..count)
+        .map(|i| ifd + 2 + i * ENTRY_SIZE)
+        .find(|entry| read_u16(exif, *entry, little_endian) == Some(tag))
+}
+
+/// Returns the range of the value of the IFD entry at the given offset.
+/// Values of up to four bytes are stored in the entry itself.
+fn get_value_range(
+    exif: &[u8],
+    entry: usize,
+    little_endian: bool,
+) -> Option<Range<usize>> {
+    let value_size = match read_u16(exif, entry + 2, little_endian)? {
+        1 | 2 | 6 | 7 => 1,
+        3 | 8 => 2,
+        4 | 9 | 11 | 13 => 4,
+        5 | 10 | 12 => 8,
+        _ => return None,
+    };
+    let count = read_u32(exif, entry + 4, little_endian)? as usize;
+    let size = count.checked_mul(value_size)?;
+    let start = match size <= 4 {
+        true => entry + 8,
+        false => read_u32(exif, entry + 8, little_endian)? as usize,
+    };
+    Some(start..start.checked_add(size)?)
+}
+
+/// Reads an unsigned 16 bit integer at the given offset.
+fn read_u16(exif: &[u8], offset: usize, little_endian: bool) -> Option<u16> {
+    let bytes = exif.get(offset..offset.checked_add(2)?)?.try_into().ok()?;
+    match little_endian {
+        true => Some(u16::from_le_bytes(bytes)),
+        false => Some(u16::from_be_bytes(bytes)),
+    }
+}
+
+/// Reads an unsigned 32 bit integer at the given offset.
+fn read_u32(exif: &[u8], offset: usize, little_endian: bool) -> Option<u32> {
+    let bytes = exif.get(offset..offset.checked_add(4)?)?.try_into().ok()?;
+    match little_endian {
+        true => Some(u32::from_le_bytes(bytes)),
+        false => Some(u32::from_be_bytes(bytes)),
+    }
+}
+
+/// Appends an unsigned 16 bit integer.
+fn write_u16(exif: &mut Vec<u8>, value: u16, little_endian: bool) {
+    match little_endian {
+        true => exif.extend_from_slice(&value.to_le_bytes()),
+        false => exif.extend_from_slice(&value.to_be_bytes()),
+    }
+}
+
+/// Appends an unsigned 32 bit integer.
+fn write_u32(exif: &mut Vec<u8>, value: u32, little_endian: bool) {
+    match little_endian {
+        true => exif.extend_from_slice(&value.to_le_bytes()),
+        false => exif.extend_from_slice(&value.to_be_bytes()),
+    }
+}
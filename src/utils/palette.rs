@@ -0,0 +1,113 @@
+use image::DynamicImage;
+
+/// Maximum width and height of the image the palette is calculated from.
+const PALETTE_SAMPLE_SIZE: u32 = 64;
+/// Pixels with an alpha value below are ignored during palette extraction.
+const PALETTE_MIN_ALPHA: u8 = 128;
+
+/// Extracts a palette of at most `max_colors` colors using the median cut
+/// algorithm. The colors are ordered by the amount of pixels they represent,
+/// so the first one is the dominant color of the image.
+/// ## Example
+///
+/// ```
+/// use {
+///     html5_picture::utils::palette::{extract_palette, to_hex},
+///     image::{DynamicImage, Rgb, RgbImage},
+/// };
+///
+/// let img = RgbImage::from_fn(30, 10, |x, _| {
+///     if x < 20 { Rgb([255, 0, 0]) } else { Rgb([0, 0, 255]) }
+/// });
+/// let palette = extract_palette(&DynamicImage::ImageRgb8(img), 2);
+/// assert_eq!(to_hex(&palette[0]), "#ff0000");
+/// assert_eq!(to_hex(&palette[1]), "#0000ff");
+/// ```
+pub fn extract_palette(img: &DynamicImage, max_colors: u8) -> Vec<[u8; 3]> {
+    let sample = if img.width() > PALETTE_SAMPLE_SIZE
+        || img.height() > PALETTE_SAMPLE_SIZE
+    {
+        img.thumbnail(PALETTE_SAMPLE_SIZE, PALETTE_SAMPLE_SIZE)
+            .to_rgba8()
+    } else {
+        img.to_rgba8()
+    };
+    let pixels: Vec<[u8; 3]> = sample
+        .pixels()
+        .filter(|p| p[3] >= PALETTE_MIN_ALPHA)
+        .map(|p| [p[0], p[1], p[2]])
+        .collect();
+    if pixels.is_empty() || max_colors == 0 {
+        return vec![];
+    }
+
+    let mut boxes = vec![pixels];
+    while boxes.len() < max_colors as usize {
+        // split the box that has the widest range weighted by its pixel count
+        let candidate = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(idx, b)| {
+                let (channel, range) = widest_channel(b);
+                (idx, channel, range as usize * b.len())
+            })
+            .filter(|(_, _, score)| *score > 0)
+            .max_by_key(|(_, _, score)| *score);
+        let (idx, channel) = match candidate {
+            Some((idx, channel, _)) => (idx, channel),
+            None => break,
+        };
+        let mut color_box = boxes.swap_remove(idx);
+        color_box.sort_unstable_by_key(|p| p[channel]);
+        // split at the median, but never between pixels of the same value
+        let median = color_box[color_box.len() / 2][channel];
+        let mut split_idx = color_box.partition_point(|p| p[channel] < median);
+        if split_idx == 0 {
+            split_idx = color_box.partition_point(|p| p[channel] <= median);
+        }
+        let upper = color_box.split_off(split_idx);
+        boxes.push(color_box);
+        boxes.push(upper);
+    }
+
+    boxes.sort_by_key(|b| std::cmp::Reverse(b.len()));
+    boxes.iter().map(|b| average_color(b)).collect()
+}
+
+/// Formats the given color as CSS hex string.
+pub fn to_hex(color: &[u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+/// Returns the channel having the widest range and the range itself.
+fn widest_channel(pixels: &[[u8; 3]]) -> (usize, u8) {
+    let mut min = [u8::MAX; 3];
+    let mut max = [u8::MIN; 3];
+    for p in pixels {
+        for c in 0..3 {
+            min[c] = min[c].min(p[c]);
+            max[c] = max[c].max(p[c]);
+        }
+    }
+    (0..3)
+        .map(|c| (c, max[c] - min[c]))
+        .max_by_key(|(_, range)| *range)
+        .unwrap()
+}
+
+/// Calculates the average color of the given pixels.
+fn average_color(pixels: &[[u8; 3]]) -> [u8; 3] {
+    let mut sum = [0u64; 3];
+    for p in pixels {
+        for c in 0..3 {
+            sum[c] += p[c] as u64;
+        }
+    }
+    let len = pixels.len() as u64;
+    [
+        ((sum[0] + len / 2) / len) as u8,
+        ((sum[1] + len / 2) / len) as u8,
+        ((sum[2] + len / 2) / len) as u8,
+    ]
+}
@@ -0,0 +1,82 @@
+use {
+    base64::{Engine, engine::general_purpose::STANDARD},
+    clap::ArgEnum,
+    image::DynamicImage,
+    serde::{Deserialize, Serialize},
+};
+
+/// Maximum width and height of the image that is embedded as data uri.
+const DATA_URI_SIZE: u32 = 24;
+/// Quality of the webp image that is embedded as data uri.
+const DATA_URI_QUALITY: f32 = 40.0;
+/// The sigma that is used to blur the data uri image.
+const DATA_URI_BLUR_SIGMA: f32 = 1.0;
+/// Maximum width and height of the image the BlurHash is calculated from.
+const BLURHASH_SIZE: u32 = 32;
+/// Horizontal and vertical components of the BlurHash.
+const BLURHASH_COMPONENTS: (u32, u32) = (4, 3);
+
+/// The available low quality image placeholder types.
+#[derive(ArgEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum PlaceholderType {
+    /// A tiny blurred webp image that is embedded as base64 data uri.
+    Webp,
+    /// A [BlurHash](https://blurha.sh) string.
+    Blurhash,
+}
+
+/// A low quality image placeholder that can be shown while the real image
+/// is loading.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Placeholder {
+    /// Contains a base64 encoded webp data uri.
+    DataUri(String),
+    /// Contains a BlurHash string.
+    BlurHash(String),
+}
+
+impl Placeholder {
+    /// Creates a placeholder of the given type from the image.
+    pub fn from(
+        img: &DynamicImage,
+        placeholder_type: PlaceholderType,
+    ) -> Result<Self, String> {
+        match placeholder_type {
+            PlaceholderType::Webp => Self::create_data_uri(img),
+            PlaceholderType::Blurhash => Self::create_blurhash(img),
+        }
+    }
+
+    /// Downscales and blurs the image and encodes it as webp data uri.
+    fn create_data_uri(img: &DynamicImage) -> Result<Self, String> {
+        let tiny = img.thumbnail(DATA_URI_SIZE, DATA_URI_SIZE);
+        let tiny = DynamicImage::ImageRgba8(image::imageops::blur(
+            &tiny.to_rgba8(),
+            DATA_URI_BLUR_SIGMA,
+        ));
+        let encoder = match webp::Encoder::from_image(&tiny) {
+            Ok(e) => e,
+            Err(msg) => return Err(msg.to_string()),
+        };
+        let encoded = encoder.encode(DATA_URI_QUALITY);
+        Ok(Self::DataUri(format!(
+            "data:image/webp;base64,{}",
+            STANDARD.encode(&*encoded)
+        )))
+    }
+
+    /// Calculates the BlurHash of the image.
+    fn create_blurhash(img: &DynamicImage) -> Result<Self, String> {
+        let tiny = img.thumbnail(BLURHASH_SIZE, BLURHASH_SIZE).to_rgba8();
+        match blurhash::encode(
+            BLURHASH_COMPONENTS.0,
+            BLURHASH_COMPONENTS.1,
+            tiny.width(),
+            tiny.height(),
+            tiny.as_raw(),
+        ) {
+            Ok(hash) => Ok(Self::BlurHash(hash)),
+            Err(msg) => Err(msg.to_string()),
+        }
+    }
+}
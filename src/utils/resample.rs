@@ -0,0 +1,579 @@
+//! High quality resampling of images. The pixels are converted to linear
+//! light and premultiplied by their alpha before they are filtered, so fine
+//! detail keeps its brightness and transparent pixels do not bleed their
+//! color into the visible edges.
+//!
+//! The source image is converted once into a ```PreparedImage```, all of its
+//! versions are resized from it. The rows are resampled in parallel. The
+//! inner loops are plain Rust that is autovectorized by the compiler; by
+//! `multiversion` they are compiled once per instruction set of common CPUs
+//! and the best version is selected at runtime.
+
+use {
+    super::{crop::CropRect, imageops::get_dither_threshold},
+    clap::ArgEnum,
+    image::{DynamicImage, Rgb32FImage, RgbImage, RgbaImage},
+    multiversion::multiversion,
+    rayon::prelude::*,
+    serde::{Deserialize, Serialize},
+};
+
+/// Standard deviation in pixels of the blur used by the unsharp mask.
+const SHARPEN_SIGMA: f32 = 1.0;
+
+/// Amount of entries of the table converting the resized values to 8 bit.
+const ENCODING_TABLE_SIZE: usize = 1 << 14;
+
+/// Minimum ratio between the size of a previous version and the requested
+/// size, for the previous version to be used as source by the cascade
+/// strategy. Below, the softening of both filters adds up visibly.
+pub const CASCADE_MIN_RATIO: u32 = 2;
+
+/// The filter that is used to calculate the resized pixels.
+#[derive(ArgEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ResizeFilter {
+    /// Linear interpolation, fast but soft.
+    Triangle,
+    /// Cubic filter that keeps the image sharp.
+    CatmullRom,
+    /// Cubic filter balancing sharpness and ringing.
+    Mitchell,
+    /// Windowed sinc filter, the sharpest one with slight ringing.
+    Lanczos3,
+}
+
+impl ResizeFilter {
+    /// Returns the radius of the filter in source pixels when upscaling.
+    fn get_support(&self) -> f32 {
+        match self {
+            Self::Triangle => 1.0,
+            Self::CatmullRom | Self::Mitchell => 2.0,
+            Self::Lanczos3 => 3.0,
+        }
+    }
+
+    /// Evaluates the filter kernel at the given distance.
+    fn get_weight(&self, x: f32) -> f32 {
+        let x = x.abs();
+        match self {
+            Self::Triangle => (1.0 - x).max(0.0),
+            Self::CatmullRom => cubic(x, 0.0, 0.5),
+            Self::Mitchell => cubic(x, 1.0 / 3.0, 1.0 / 3.0),
+            Self::Lanczos3 => match x < 3.0 {
+                true => sinc(x) * sinc(x / 3.0),
+                false => 0.0,
+            },
+        }
+    }
+}
+
+/// Determines the source of the versions of an image.
+#[derive(ArgEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ResizeStrategy {
+    /// Every version is resized from the original image.
+    Original,
+    /// Every version is resized from the previous, larger version if it is
+    /// at least twice as large, otherwise from the original image.
+    Cascade,
+}
+
+/// The parameter of the resizing.
+#[derive(Clone, Copy, Debug)]
+pub struct ResizeParameter {
+    pub filter: ResizeFilter,
+    /// If true, the image is resized in linear light instead of sRGB gamma
+    /// space.
+    pub linear_light: bool,
+    /// Amount of the unsharp mask applied after resizing, e.g. 0.5. Unlike
+    /// the resizing, it runs in sRGB gamma space on the 8 bit pixels, as
+    /// halos are less visible there.
+    pub sharpen: Option<f32>,
+    /// The source the versions are resized from.
+    pub strategy: ResizeStrategy,
+}
+
+impl Default for ResizeParameter {
+    fn default() -> Self {
+        Self {
+            filter: ResizeFilter::Lanczos3,
+            linear_light: true,
+            sharpen: None,
+            strategy: ResizeStrategy::Cascade,
+        }
+    }
+}
+
+/// Resizes the image to exactly the given dimensions. Images without alpha
+/// channel are returned as RGB8, all others as RGBA8.
+/// ## Example
+///
+/// ```
+/// use html5_picture::utils::resample::{ResizeParameter, resize};
+///
+/// let img = image::DynamicImage::new_rgba8(40, 20);
+/// let resized = resize(&img, 10, 5, &ResizeParameter::default());
+/// assert_eq!((resized.width(), resized.height()), (10, 5));
+/// ```
+pub fn resize(
+    img: &DynamicImage,
+    width: u32,
+    height: u32,
+    params: &ResizeParameter,
+) -> DynamicImage {
+    PreparedImage::new(img, params)
+        .resize(width, height)
+        .to_image()
+}
+
+/// An image converted to the working space of the resampler, premultiplied
+/// RGBA floats in linear light if enabled. Converting the source once and
+/// resizing all versions from the prepared image avoids decoding the pixels
+/// again for every version.
+///
+/// Every pixel is stored as `[f32; 4]`, i.e. 16 bytes. A 6000x4000 photo
+/// takes about 384 MB, in addition to the decoded source image. Up to
+/// `MAX_CONCURRENT_IMAGES` (4) images are processed at the same time, so
+/// converting large photos requires at least 1.5 GB of memory.
+/// ## Example
+///
+/// ```
+/// use html5_picture::utils::resample::{PreparedImage, ResizeParameter};
+///
+/// let img = image::DynamicImage::new_rgb8(600, 400);
+/// let prepared = PreparedImage::new(&img, &ResizeParameter::default());
+/// let large = prepared.resize(300, 200);
+/// // the smaller version is derived from the larger one
+/// let small = large.resize(150, 100).to_image();
+/// assert_eq!((small.width(), small.height()), (150, 100));
+/// ```
+#[derive(Clone, Debug)]
+pub struct PreparedImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<[f32; 4]>,
+    has_alpha: bool,
+    /// True if the source has more than 8 bit per channel, its gradients
+    /// are dithered when converted back to 8 bit.
+    dither: bool,
+    params: ResizeParameter,
+}
+
+impl PreparedImage {
+    /// Converts the image to the working space given by the parameter.
+    pub fn new(img: &DynamicImage, params: &ResizeParameter) -> Self {
+        let table = get_decoding_table(params.linear_light);
+        let pixels = match img {
+            // 8 bit images are decoded by a lookup table
+            DynamicImage::ImageRgb8(buffer) => buffer
+                .as_raw()
+                .par_chunks_exact(3)
+                .map(|p| {
+                    let (r, g, b) = (p[0], p[1], p[2]);
+                    [
+                        table[r as usize],
+                        table[g as usize],
+                        table[b as usize],
+                        1.0,
+                    ]
+                })
+                .collect(),
+            DynamicImage::ImageRgba8(buffer) => buffer
+                .as_raw()
+                .par_chunks_exact(4)
+                .map(|p| {
+                    premultiply([
+                        table[p[0] as usize],
+                        table[p[1] as usize],
+                        table[p[2] as usize],
+                        p[3] as f32 / 255.0,
+                    ])
+                })
+                .collect(),
+            _ => img
+                .to_rgba32f()
+                .as_raw()
+                .par_chunks_exact(4)
+                .map(|p| {
+                    let mut pixel = [p[0], p[1], p[2], p[3]];
+                    if params.linear_light {
+                        for value in pixel.iter_mut().take(3) {
+                            *value = srgb_to_linear(*value);
+                        }
+                    }
+                    premultiply(pixel)
+                })
+                .collect(),
+        };
+        let dither = !matches!(
+            img,
+            DynamicImage::ImageRgb8(_)
+                | DynamicImage::ImageRgba8(_)
+                | DynamicImage::ImageLuma8(_)
+                | DynamicImage::ImageLumaA8(_)
+        );
+        Self {
+            width: img.width(),
+            height: img.height(),
+            pixels,
+            has_alpha: img.color().has_alpha(),
+            dither,
+            params: *params,
+        }
+    }
+
+    /// Returns the width of the image.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Returns the height of the image.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the given part of the image.
+    pub fn crop(&self, rect: &CropRect) -> Self {
+        let x = rect.x.min(self.width) as usize;
+        let y = rect.y.min(self.height) as usize;
+        let width = (rect.width as usize).min(self.width as usize - x);
+        let height = (rect.height as usize).min(self.height as usize - y);
+        let pixels = self
+            .pixels
+            .chunks_exact(self.width as usize)
+            .skip(y)
+            .take(height)
+            .flat_map(|row| &row[x..x + width])
+            .copied()
+            .collect();
+        Self {
+            width: width as u32,
+            height: height as u32,
+            pixels,
+            has_alpha: self.has_alpha,
+            dither: self.dither,
+            params: self.params,
+        }
+    }
+
+    /// Resamples the image to exactly the given dimensions. The result stays
+    /// in the working space, so it can be the source of further versions.
+    pub fn resize(&self, width: u32, height: u32) -> Self {
+        let (width, height) = (width.max(1), height.max(1));
+        let filter = self.params.filter;
+        let horizontal = |pixels: &[[f32; 4]]| {
+            resample_horizontal(
+                pixels,
+                self.width as usize,
+                width as usize,
+                filter,
+            )
+        };
+        let vertical = |pixels: &[[f32; 4]], row_width: u32| {
+            resample_vertical(
+                pixels,
+                row_width as usize,
+                self.height,
+                height,
+                filter,
+            )
+        };
+        // the passes are skipped if the size does not change in their
+        // direction
+        let pixels = match (width == self.width, height == self.height) {
+            (true, true) => self.pixels.clone(),
+            (true, false) => vertical(&self.pixels, self.width),
+            (false, true) => horizontal(&self.pixels),
+            // the pass shrinking the image most runs first, so the
+            // intermediate image is the smaller one
+            (false, false)
+                if width as u64 * self.height as u64
+                    > self.width as u64 * height as u64 =>
+            {
+                horizontal(&vertical(&self.pixels, self.width))
+            }
+            (false, false) => vertical(&horizontal(&self.pixels), width),
+        };
+        Self {
+            width,
+            height,
+            pixels,
+            has_alpha: self.has_alpha,
+            dither: self.dither,
+            params: self.params,
+        }
+    }
+
+    /// Converts the image back to sRGB and applies the sharpening. Images
+    /// without alpha channel are returned as RGB8, all others as RGBA8.
+    /// Sources with more than 8 bit per channel are dithered, so their
+    /// gradients do not band.
+    pub fn to_image(&self) -> DynamicImage {
+        let channels = match self.has_alpha {
+            true => 4,
+            false => 3,
+        };
+        let table = get_encoding_table(self.params.linear_light);
+        let scale = (ENCODING_TABLE_SIZE - 1) as f32;
+        let width = self.width as usize;
+        let mut raw = vec![0u8; self.pixels.len() * channels];
+        raw.par_chunks_exact_mut(channels)
+            .zip(self.pixels.par_iter())
+            .enumerate()
+            .for_each(|(idx, (dst, src))| {
+                let threshold = match self.dither {
+                    true => get_dither_threshold(idx % width, idx / width),
+                    false => 0.0,
+                };
+                let alpha = src[3].clamp(0.0, 1.0);
+                for c in 0..3 {
+                    let value = match alpha > 0.0 {
+                        true => (src[c] / alpha).clamp(0.0, 1.0),
+                        false => 0.0,
+                    };
+                    // interpolates between the entries of the table
+                    let position = value * scale;
+                    let lower = position as usize;
+                    let upper = (lower + 1).min(ENCODING_TABLE_SIZE - 1);
+                    let encoded = table[lower]
+                        + (table[upper] - table[lower])
+                            * (position - lower as f32);
+                    dst[c] =
+                        (encoded + threshold).round().clamp(0.0, 255.0) as u8;
+                }
+                if channels == 4 {
+                    dst[3] = (alpha * 255.0 + threshold)
+                        .round()
+                        .clamp(0.0, 255.0) as u8;
+                }
+            });
+        if let Some(amount) = self.params.sharpen {
+            sharpen(&mut raw, channels, self.width, self.height, amount);
+        }
+        // the buffer size always matches the dimensions
+        match self.has_alpha {
+            true => DynamicImage::ImageRgba8(
+                RgbaImage::from_raw(self.width, self.height, raw).unwrap(),
+            ),
+            false => DynamicImage::ImageRgb8(
+                RgbImage::from_raw(self.width, self.height, raw).unwrap(),
+            ),
+        }
+    }
+}
+
+/// The source pixels and their weights of a resampled pixel.
+struct Contribution {
+    start: usize,
+    weights: Vec<f32>,
+}
+
+/// Calculates the source pixels and their weights for every pixel of a line
+/// that is resampled from `src_len` to `dst_len` pixels.
+fn get_contributions(
+    src_len: u32,
+    dst_len: u32,
+    filter: ResizeFilter,
+) -> Vec<Contribution> {
+    let scale = src_len as f32 / dst_len as f32;
+    // the filter is stretched when downscaling to cover all source pixels
+    let filter_scale = scale.max(1.0);
+    let support = filter.get_support() * filter_scale;
+    (0..dst_len)
+        .map(|idx| {
+            let center = (idx as f32 + 0.5) * scale;
+            let start = ((center - support).floor().max(0.0)) as usize;
+            let end =
+                ((center + support).ceil() as usize).min(src_len as usize);
+            let mut weights: Vec<f32> = (start..end)
+                .map(|src| {
+                    filter
+                        .get_weight((src as f32 + 0.5 - center) / filter_scale)
+                })
+                .collect();
+            let sum: f32 = weights.iter().sum();
+            if sum != 0.0 {
+                weights.iter_mut().for_each(|w| *w /= sum);
+            }
+            Contribution { start, weights }
+        })
+        .collect()
+}
+
+/// Resamples the rows of the image to the given width. The rows are
+/// processed in parallel.
+fn resample_horizontal(
+    pixels: &[[f32; 4]],
+    src_width: usize,
+    width: usize,
+    filter: ResizeFilter,
+) -> Vec<[f32; 4]> {
+    let contributions =
+        get_contributions(src_width as u32, width as u32, filter);
+    let mut resampled = vec![[0f32; 4]; pixels.len() / src_width * width];
+    resampled
+        .par_chunks_exact_mut(width)
+        .zip(pixels.par_chunks_exact(src_width))
+        .for_each(|(dst_row, src_row)| {
+            resample_row(dst_row, src_row, &contributions)
+        });
+    resampled
+}
+
+/// Resamples a single row by the given contributions.
+#[multiversion(targets = "simd")]
+fn resample_row(
+    dst_row: &mut [[f32; 4]],
+    src_row: &[[f32; 4]],
+    contributions: &[Contribution],
+) {
+    for (dst, contribution) in dst_row.iter_mut().zip(contributions) {
+        let src = &src_row[contribution.start..];
+        let mut sum = [0f32; 4];
+        for (p, weight) in src.iter().zip(&contribution.weights) {
+            for c in 0..4 {
+                sum[c] += p[c] * weight;
+            }
+        }
+        *dst = sum;
+    }
+}
+
+/// Resamples the columns of the image to the given height. Every resampled
+/// row is the weighted sum of whole source rows, which keeps the memory
+/// access sequential and lets the compiler vectorize the inner loop.
+fn resample_vertical(
+    pixels: &[[f32; 4]],
+    width: usize,
+    src_height: u32,
+    height: u32,
+    filter: ResizeFilter,
+) -> Vec<[f32; 4]> {
+    let contributions = get_contributions(src_height, height, filter);
+    let mut resampled = vec![[0f32; 4]; width * height as usize];
+    resampled
+        .par_chunks_exact_mut(width)
+        .zip(contributions.par_iter())
+        .for_each(|(dst_row, contribution)| {
+            for (idx, weight) in contribution.weights.iter().enumerate() {
+                let start = (contribution.start + idx) * width;
+                let src_row = pixels[start..start + width].as_flattened();
+                add_weighted_row(dst_row.as_flattened_mut(), src_row, *weight);
+            }
+        });
+    resampled
+}
+
+/// Adds the source row multiplied by the weight to the resampled row.
+#[multiversion(targets = "simd")]
+fn add_weighted_row(dst_row: &mut [f32], src_row: &[f32], weight: f32) {
+    for (dst, p) in dst_row.iter_mut().zip(src_row) {
+        *dst += p * weight;
+    }
+}
+
+/// Applies an unsharp mask with the given amount to the color channels of
+/// the 8 bit pixels. The mask is calculated in gamma space, as halos are less
+/// visible there.
+fn sharpen(
+    pixels: &mut [u8],
+    channels: usize,
+    width: u32,
+    height: u32,
+    amount: f32,
+) {
+    let mut colors = Rgb32FImage::new(width, height);
+    for (src, dst) in pixels.chunks_exact(channels).zip(colors.pixels_mut()) {
+        for c in 0..3 {
+            dst[c] = src[c] as f32 / 255.0;
+        }
+    }
+    let blurred = image::imageops::blur(&colors, SHARPEN_SIGMA);
+    for (p, b) in pixels.chunks_exact_mut(channels).zip(blurred.pixels()) {
+        for c in 0..3 {
+            let value = p[c] as f32 / 255.0;
+            let value = (value + amount * (value - b[c])).clamp(0.0, 1.0);
+            p[c] = (value * 255.0).round() as u8;
+        }
+    }
+}
+
+/// Multiplies the color channels by the alpha channel.
+fn premultiply(mut pixel: [f32; 4]) -> [f32; 4] {
+    for c in 0..3 {
+        pixel[c] *= pixel[3];
+    }
+    pixel
+}
+
+/// Returns the working space values of all 8 bit channel values.
+fn get_decoding_table(linear_light: bool) -> [f32; 256] {
+    let mut table = [0f32; 256];
+    for (idx, value) in table.iter_mut().enumerate() {
+        *value = match linear_light {
+            true => srgb_to_linear(idx as f32 / 255.0),
+            false => idx as f32 / 255.0,
+        };
+    }
+    table
+}
+
+/// Returns the sRGB values, scaled to 0-255, of evenly spaced working space
+/// values. They are not rounded, so the values in between can be
+/// interpolated and dithered.
+fn get_encoding_table(linear_light: bool) -> Vec<f32> {
+    let scale = (ENCODING_TABLE_SIZE - 1) as f32;
+    (0..ENCODING_TABLE_SIZE)
+        .map(|idx| {
+            let value = idx as f32 / scale;
+            let value = match linear_light {
+                true => linear_to_srgb(value),
+                false => value,
+            };
+            value * 255.0
+        })
+        .collect()
+}
+
+/// Evaluates the Mitchell-Netravali cubic filter with the given parameters.
+fn cubic(x: f32, b: f32, c: f32) -> f32 {
+    let value = if x < 1.0 {
+        (12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+            + (-18.0 + 12.0 * b + 6.0 * c) * x.powi(2)
+            + (6.0 - 2.0 * b)
+    } else if x < 2.0 {
+        (-b - 6.0 * c) * x.powi(3)
+            + (6.0 * b + 30.0 * c) * x.powi(2)
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c)
+    } else {
+        0.0
+    };
+    value / 6.0
+}
+
+/// The normalized sinc function.
+fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        return 1.0;
+    }
+    let x = x * std::f32::consts::PI;
+    x.sin() / x
+}
+
+/// Converts a sRGB encoded value to linear light.
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts a value in linear light to sRGB encoding.
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
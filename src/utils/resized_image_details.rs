@@ -1,43 +1,46 @@
-use std::path::PathBuf;
+use {
+    crate::utils::{
+        ImageInfo, crop::Crop, derivative::Derivative, sidecar::ArtDirection,
+    },
+    std::path::{Path, PathBuf},
+};
 /// Contains the determined image details required for conversion.
 #[derive(Debug)]
 pub struct ResizedImageDetails {
     pub output_file_name: PathBuf,
     pub width: u32,
     pub height: u32,
+    /// The part of the source image that is used, if it is cropped.
+    pub crop: Option<Crop>,
 }
 
 impl ResizedImageDetails {
+    /// The maximum pixel density that is created in pixel density mode.
+    pub const MAX_PIXEL_DENSITY: u8 = 3;
+
     /// Creates a new instance.
     pub fn new(output_file_name: PathBuf, width: u32, height: u32) -> Self {
         Self {
             output_file_name,
             width,
             height,
+            crop: None,
         }
     }
 
     /// Calculates height, width and output file names for the scaled images.
     pub fn from(
         image_file_name: &PathBuf,
+        info: &ImageInfo,
         scaled_images_count: u8,
     ) -> Result<Vec<ResizedImageDetails>, String> {
-        // get image dimensions
-        let (w, _) = match image::image_dimensions(&image_file_name) {
-            Err(msg) => return Err(msg.to_string()),
-            Ok((w, h)) => (w, h),
-        };
         // calculate a step in pixel that is used to calculate the new width
-        let step = w as f32 / (scaled_images_count as f32 + 1.0);
+        let step = info.width as f32 / (scaled_images_count as f32 + 1.0);
         let mut resized_details = vec![];
         for idx in 0..scaled_images_count {
             let new_width = (idx + 1) as f32 * step;
             let new_width = math::round::ceil(new_width.into(), 0) as u32;
-            let new_height =
-                crate::utils::imageops::calculate_height_preserve_aspect_ratio(
-                    &image_file_name,
-                    new_width,
-                )?;
+            let new_height = info.get_height(new_width);
             let output_file_name =
                 Self::get_resized_file_name(&image_file_name, new_width)?;
             resized_details.push(ResizedImageDetails::new(
@@ -49,23 +52,151 @@ impl ResizedImageDetails {
         Ok(resized_details)
     }
 
-    /// Constructs the file name for a resized image.
-    pub fn get_resized_file_name(
+    /// Calculates height, width and output file names for the art directed
+    /// crops. The result is sorted by the breakpoint of the crops.
+    pub fn from_art_direction(
         image_file_name: &PathBuf,
-        width: u32,
+        info: &ImageInfo,
+        art_direction: &[ArtDirection],
+    ) -> Result<Vec<ResizedImageDetails>, String> {
+        if art_direction.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut art_direction = art_direction.to_vec();
+        art_direction.sort_by_key(|a| a.max_width);
+        let mut resized_details = vec![];
+        for art in art_direction {
+            let (crop_width, crop_height) =
+                art.crop.get_size(info.width, info.height);
+            // never upscale the cropped area
+            let new_width = art.max_width.min(crop_width);
+            let new_height = ((new_width as f64 / crop_width as f64)
+                * crop_height as f64)
+                .round()
+                .max(1.0) as u32;
+            let output_file_name = Self::get_art_direction_file_name(
+                &image_file_name,
+                art.max_width,
+            )?;
+            let mut details = ResizedImageDetails::new(
+                output_file_name,
+                new_width,
+                new_height,
+            );
+            details.crop = Some(art.crop);
+            resized_details.push(details);
+        }
+        Ok(resized_details)
+    }
+
+    /// Calculates height, width and output file names for images that are
+    /// displayed with a fixed CSS width. One image per pixel density up to
+    /// `max_density` is created, densities exceeding the source image are
+    /// skipped. The result is ordered by ascending density.
+    /// If the source image is smaller than `css_width`, it is used as `1x`.
+    pub fn from_pixel_density(
+        image_file_name: &PathBuf,
+        info: &ImageInfo,
+        css_width: u32,
+        max_density: u8,
+    ) -> Result<Vec<ResizedImageDetails>, String> {
+        if css_width == 0 {
+            return Err("css_width must be > 0".to_string());
+        }
+        let w = info.width;
+        let mut resized_details = vec![];
+        for density in 1..=max_density {
+            // such widths exceed any source image
+            let new_width = match css_width.checked_mul(density as u32) {
+                Some(v) => v,
+                None => break,
+            };
+            if new_width > w && density > 1 {
+                break;
+            }
+            let new_width = new_width.min(w);
+            let new_height = info.get_height(new_width);
+            let output_file_name =
+                Self::get_pixel_density_file_name(image_file_name, density)?;
+            resized_details.push(ResizedImageDetails::new(
+                output_file_name,
+                new_width,
+                new_height,
+            ));
+        }
+        Ok(resized_details)
+    }
+
+    /// Constructs the file name for an image of the given pixel density.
+    pub fn get_pixel_density_file_name(
+        image_file_name: &Path,
+        density: u8,
+    ) -> Result<PathBuf, String> {
+        let file_name = Self::get_file_stem(image_file_name)?;
+        Ok(PathBuf::from(format!("{}-{}x.webp", file_name, density)))
+    }
+
+    /// Calculates height, width and output file names for the images of the
+    /// given derivative. Widths exceeding the cropped source image are
+    /// replaced by the width of the cropped source image.
+    pub fn from_derivative(
+        image_file_name: &Path,
+        info: &ImageInfo,
+        derivative: &Derivative,
+    ) -> Result<Vec<ResizedImageDetails>, String> {
+        let crop = Crop::AspectRatio(derivative.aspect_ratio);
+        let (crop_width, _) = crop.get_size(info.width, info.height);
+        let mut widths: Vec<u32> = derivative
+            .widths
+            .iter()
+            .map(|width| (*width).min(crop_width))
+            .collect();
+        widths.dedup();
+        let mut resized_details = vec![];
+        for width in widths {
+            let output_file_name =
+                derivative.get_file_name(image_file_name, width)?;
+            let mut details = ResizedImageDetails::new(
+                output_file_name,
+                width,
+                derivative.aspect_ratio.get_height(width),
+            );
+            details.crop = Some(crop.clone());
+            resized_details.push(details);
+        }
+        Ok(resized_details)
+    }
+
+    /// Constructs the file name for an art directed crop.
+    pub fn get_art_direction_file_name(
+        image_file_name: &Path,
+        max_width: u32,
     ) -> Result<PathBuf, String> {
+        let file_name = Self::get_file_stem(image_file_name)?;
+        Ok(PathBuf::from(format!(
+            "{}-art{}.webp",
+            file_name, max_width
+        )))
+    }
+
+    /// Returns the utf-8 checked file stem of the given image.
+    fn get_file_stem(image_file_name: &Path) -> Result<String, String> {
         let file_name = match &image_file_name.file_stem() {
             Some(f) => f.to_owned(),
             None => return Err("No filename given!".to_string()),
         };
-        let file_name = match file_name.to_str() {
-            Some(f) => f,
-            None => {
-                return Err(
-                    "utf-8 check failed for resized filename".to_string()
-                )
-            }
-        };
+        match file_name.to_str() {
+            Some(f) => Ok(f.to_string()),
+            None => Err("utf-8 check failed for resized filename".to_string()),
+        }
+    }
+
+    /// Constructs the file name for a resized image.
+    pub fn get_resized_file_name(
+        image_file_name: &Path,
+        width: u32,
+    ) -> Result<PathBuf, String> {
+        let file_name = Self::get_file_stem(image_file_name)?;
         Ok(PathBuf::from(format!("{}-w{}.webp", file_name, width)))
     }
 }
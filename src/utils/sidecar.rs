@@ -0,0 +1,93 @@
+use {
+    crate::utils::crop::{Crop, FocalPoint},
+    serde::{Deserialize, Serialize},
+    std::path::{Path, PathBuf},
+};
+
+/// Suffix of the sidecar file that is stored next to the source image.
+const SIDECAR_FILE_SUFFIX: &str = "picture.json";
+
+/// An art directed crop of the image that is used for viewports up to the
+/// given width.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ArtDirection {
+    /// The crop is used for viewports up to this width in px.
+    pub max_width: u32,
+    /// The part of the image that is shown.
+    pub crop: Crop,
+}
+
+/// Contains user defined settings for a single image. It is read from a JSON
+/// file next to the source image, for `hero.png` this is `hero.picture.json`:
+///
+/// ```json
+/// {
+///     "focal_point": { "x": 0.3, "y": 0.4 },
+///     "art_direction": [
+///         { "max_width": 600, "crop": { "aspect_ratio": "4:5" } },
+///         {
+///             "max_width": 1000,
+///             "crop": { "rect": { "x": 200, "y": 0, "width": 1600, "height": 900 } }
+///         }
+///     ]
+/// }
+/// ```
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct Sidecar {
+    /// The point of interest that is kept when cropping to an aspect ratio.
+    /// Overrides the focal point given in the XMP metadata of the image.
+    #[serde(default)]
+    pub focal_point: Option<FocalPoint>,
+    /// Art directed crops of the image.
+    #[serde(default)]
+    pub art_direction: Vec<ArtDirection>,
+}
+
+impl Sidecar {
+    /// Loads the sidecar that belongs to the given image. Returns the default
+    /// settings if no file exists.
+    pub fn from(image_file_name: &Path) -> Result<Self, String> {
+        let file_name = Self::get_file_name(image_file_name)?;
+        if !file_name.is_file() {
+            return Ok(Self::default());
+        }
+        let content = match std::fs::read_to_string(&file_name) {
+            Ok(c) => c,
+            Err(msg) => return Err(msg.to_string()),
+        };
+        match serde_json::from_str(&content) {
+            Ok(v) => Ok(v),
+            Err(msg) => Err(format!("{}: {}", file_name.display(), msg)),
+        }
+    }
+
+    /// Constructs the file name of the sidecar file for the given image.
+    /// ## Example
+    ///
+    /// ```
+    /// use {
+    ///     html5_picture::utils::Sidecar,
+    ///     std::path::PathBuf,
+    /// };
+    ///
+    /// let image = PathBuf::from("assets/some/hero.png");
+    /// let sidecar = Sidecar::get_file_name(&image).unwrap();
+    /// assert_eq!(sidecar.to_str().unwrap(), "assets/some/hero.picture.json");
+    /// ```
+    pub fn get_file_name(image_file_name: &Path) -> Result<PathBuf, String> {
+        let file_name = match image_file_name.file_stem() {
+            Some(f) => f,
+            None => return Err("No filename given!".to_string()),
+        };
+        let file_name = match file_name.to_str() {
+            Some(f) => f,
+            None => {
+                return Err(
+                    "utf-8 check failed for sidecar filename".to_string()
+                );
+            }
+        };
+        Ok(image_file_name
+            .with_file_name(format!("{}.{}", file_name, SIDECAR_FILE_SUFFIX)))
+    }
+}
@@ -0,0 +1,250 @@
+//! SVG source images. They are either rasterized to responsive bitmaps that
+//! are converted like any other image, or copied as they are and referenced
+//! by a plain `<img>` tag.
+
+use {
+    crate::utils::crop::CropRect,
+    clap::ArgEnum,
+    image::{DynamicImage, RgbaImage},
+    resvg::{
+        tiny_skia::{Pixmap, Transform},
+        usvg::{self, Tree, fontdb, roxmltree},
+    },
+    serde::{Deserialize, Serialize},
+    std::{
+        path::{Path, PathBuf},
+        sync::{Arc, OnceLock},
+    },
+};
+
+/// The width SVGs without intrinsic size are rasterized at, if not given by
+/// the configuration.
+pub const DEFAULT_SVG_WIDTH: u32 = 1920;
+
+/// The system fonts used to render text, loaded once on first use.
+static FONT_DATABASE: OnceLock<Arc<fontdb::Database>> = OnceLock::new();
+
+/// Determines how SVG images are processed.
+#[derive(
+    ArgEnum, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq,
+)]
+pub enum SvgMode {
+    /// Rasterizes the SVG at every breakpoint width and converts the bitmaps
+    /// to webp.
+    #[default]
+    Rasterize,
+    /// Copies the SVG as it is and references it by a plain `<img>` tag.
+    PassThrough,
+}
+
+/// A parsed SVG image.
+pub struct Svg {
+    tree: Tree,
+    width: u32,
+    height: u32,
+}
+
+impl Svg {
+    /// Parses the given SVG file. SVGs without intrinsic size, i.e. without
+    /// absolute width and height attributes, take the given width and the
+    /// height of the aspect ratio of their viewBox.
+    pub fn from(
+        image_file_name: &Path,
+        default_width: u32,
+    ) -> Result<Self, String> {
+        let data = match std::fs::read(image_file_name) {
+            Ok(d) => d,
+            Err(msg) => return Err(msg.to_string()),
+        };
+        // embedded images are referenced relative to the SVG
+        let resources_dir = image_file_name.parent().map(|p| p.to_path_buf());
+        Self::from_data(&data, resources_dir, default_width)
+    }
+
+    /// Reads the displayed size of the given SVG file, see `Svg::from`. The
+    /// system fonts are not loaded, as text does not change the size.
+    pub fn get_size(
+        image_file_name: &Path,
+        default_width: u32,
+    ) -> Result<(u32, u32), String> {
+        let data = match std::fs::read(image_file_name) {
+            Ok(d) => d,
+            Err(msg) => return Err(msg.to_string()),
+        };
+        let svg = Self::parse(&data, &usvg::Options::default(), default_width)?;
+        Ok((svg.width, svg.height))
+    }
+
+    /// Parses the given SVG data, see `Svg::from`.
+    /// ## Example
+    ///
+    /// ```
+    /// use html5_picture::utils::svg::Svg;
+    ///
+    /// let svg = br#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 4 3">
+    ///     <rect width="4" height="3" fill="red"/></svg>"#;
+    /// let svg = Svg::from_data(svg, None, 800).unwrap();
+    /// assert_eq!((svg.width(), svg.height()), (800, 600));
+    ///
+    /// let svg = br#"<svg xmlns="http://www.w3.org/2000/svg" width="40"
+    ///     height="20"/>"#;
+    /// let svg = Svg::from_data(svg, None, 800).unwrap();
+    /// assert_eq!((svg.width(), svg.height()), (40, 20));
+    /// ```
+    pub fn from_data(
+        data: &[u8],
+        resources_dir: Option<PathBuf>,
+        default_width: u32,
+    ) -> Result<Self, String> {
+        let options = usvg::Options {
+            resources_dir,
+            fontdb: get_font_database(),
+            ..Default::default()
+        };
+        Self::parse(data, &options, default_width)
+    }
+
+    /// Parses the given SVG data with the given options.
+    fn parse(
+        data: &[u8],
+        options: &usvg::Options,
+        default_width: u32,
+    ) -> Result<Self, String> {
+        let tree = match Tree::from_data(data, options) {
+            Ok(t) => t,
+            Err(msg) => return Err(msg.to_string()),
+        };
+        let size = tree.size();
+        let (width, height) = match has_intrinsic_size(data) {
+            true => (size.width().round(), size.height().round()),
+            false => (
+                default_width as f32,
+                (default_width as f32 * size.height() / size.width()).round(),
+            ),
+        };
+        Ok(Self {
+            tree,
+            width: (width as u32).max(1),
+            height: (height as u32).max(1),
+        })
+    }
+
+    /// The width the SVG is displayed at.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// The height the SVG is displayed at.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Rasterizes the SVG to an image of the given size. The crop rectangle
+    /// is given in pixels of the displayed size, the part inside of it fills
+    /// the image. As the SVG is rendered at the target size, no detail is
+    /// lost by resampling.
+    /// ## Example
+    ///
+    /// ```
+    /// use html5_picture::utils::{crop::CropRect, svg::Svg};
+    ///
+    /// let svg = br#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 2 1">
+    ///     <rect width="1" height="1" fill="red"/></svg>"#;
+    /// let svg = Svg::from_data(svg, None, 200).unwrap();
+    /// let img = svg.rasterize(None, 20, 10).unwrap().to_rgba8();
+    /// assert_eq!(img.get_pixel(2, 5).0, [255, 0, 0, 255]);
+    /// assert_eq!(img.get_pixel(17, 5).0, [0, 0, 0, 0]);
+    ///
+    /// let rect = CropRect { x: 100, y: 0, width: 100, height: 100 };
+    /// let img = svg.rasterize(Some(&rect), 10, 10).unwrap().to_rgba8();
+    /// assert_eq!(img.get_pixel(5, 5).0, [0, 0, 0, 0]);
+    /// ```
+    pub fn rasterize(
+        &self,
+        rect: Option<&CropRect>,
+        width: u32,
+        height: u32,
+    ) -> Result<DynamicImage, String> {
+        let mut pixmap = match Pixmap::new(width, height) {
+            Some(p) => p,
+            None => {
+                return Err(format!(
+                    "Invalid raster size {}x{}!",
+                    width, height
+                ));
+            }
+        };
+        let rect = match rect {
+            Some(r) => *r,
+            None => CropRect {
+                x: 0,
+                y: 0,
+                width: self.width,
+                height: self.height,
+            },
+        };
+        // scales the SVG to the displayed size, moves the crop rectangle to
+        // the origin and scales it to the size of the image
+        let size = self.tree.size();
+        let transform = Transform::from_scale(
+            self.width as f32 / size.width(),
+            self.height as f32 / size.height(),
+        )
+        .post_translate(-(rect.x as f32), -(rect.y as f32))
+        .post_scale(
+            width as f32 / rect.width.max(1) as f32,
+            height as f32 / rect.height.max(1) as f32,
+        );
+        resvg::render(&self.tree, transform, &mut pixmap.as_mut());
+        // the pixmap is premultiplied by alpha
+        let pixels = pixmap
+            .pixels()
+            .iter()
+            .flat_map(|pixel| {
+                let color = pixel.demultiply();
+                [color.red(), color.green(), color.blue(), color.alpha()]
+            })
+            .collect();
+        match RgbaImage::from_raw(width, height, pixels) {
+            Some(img) => Ok(DynamicImage::ImageRgba8(img)),
+            None => Err("Could not create image of the SVG!".to_string()),
+        }
+    }
+}
+
+/// Returns true if the root element of the given SVG has an absolute width
+/// or height. Otherwise the SVG scales to the size of its container.
+/// Compressed SVGs are assumed to have one.
+fn has_intrinsic_size(data: &[u8]) -> bool {
+    let text = match std::str::from_utf8(data) {
+        Ok(t) => t,
+        Err(_) => return true,
+    };
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+    let document = match roxmltree::Document::parse_with_options(text, options)
+    {
+        Ok(d) => d,
+        Err(_) => return true,
+    };
+    let root = document.root_element();
+    ["width", "height"]
+        .iter()
+        .any(|name| match root.attribute(*name) {
+            Some(value) => !value.trim().ends_with('%'),
+            None => false,
+        })
+}
+
+/// Returns the system fonts, loading them on first use.
+fn get_font_database() -> Arc<fontdb::Database> {
+    FONT_DATABASE
+        .get_or_init(|| {
+            let mut database = fontdb::Database::new();
+            database.load_system_fonts();
+            Arc::new(database)
+        })
+        .clone()
+}
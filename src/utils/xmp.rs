@@ -0,0 +1,208 @@
+//! Minimal support for XMP metadata embedded in the source images.
+
+use {
+    crate::utils::crop::FocalPoint,
+    std::{ops::Range, path::PathBuf},
+};
+
+const XMP_START: &str = "<x:xmpmeta";
+const XMP_END: &str = "</x:xmpmeta>";
+
+/// The properties describing the author and the rights of an image.
+const RIGHTS_PROPERTIES: [&str; 4] = [
+    "dc:creator",
+    "dc:rights",
+    "xmpRights:UsageTerms",
+    "xmpRights:WebStatement",
+];
+
+/// Reads the XMP packet that is embedded in the given image, if any.
+pub fn read_xmp(image_file_name: &PathBuf) -> Result<Option<String>, String> {
+    let content = match std::fs::read(image_file_name) {
+        Ok(c) => c,
+        Err(msg) => return Err(msg.to_string()),
+    };
+    let start = match find(&content, XMP_START.as_bytes()) {
+        Some(s) => s,
+        None => return Ok(None),
+    };
+    let end = match find(&content[start..], XMP_END.as_bytes()) {
+        Some(e) => start + e + XMP_END.len(),
+        None => return Ok(None),
+    };
+    Ok(Some(
+        String::from_utf8_lossy(&content[start..end]).to_string(),
+    ))
+}
+
+/// Reads the focal point from the XMP metadata of the given image.
+pub fn read_focal_point(
+    image_file_name: &PathBuf,
+) -> Result<Option<FocalPoint>, String> {
+    Ok(read_xmp(image_file_name)?.and_then(|xmp| parse_focal_point(&xmp)))
+}
+
+/// Parses the focal point from the given XMP packet. Regions following the
+/// schema of the Metadata Working Group (`mwg-rs`) are supported, the center
+/// of the first region is used as focal point.
+/// ## Example
+///
+/// ```
+/// use html5_picture::utils::xmp::parse_focal_point;
+///
+/// let xmp = r#"<x:xmpmeta><mwg-rs:Regions><mwg-rs:RegionList><rdf:Bag>
+///     <rdf:li><mwg-rs:Area stArea:x="0.25" stArea:y="0.75" stArea:w="0.1"
+///     stArea:h="0.1" stArea:unit="normalized"/></rdf:li>
+/// </rdf:Bag></mwg-rs:RegionList></mwg-rs:Regions></x:xmpmeta>"#;
+/// let focal_point = parse_focal_point(xmp).unwrap();
+/// assert_eq!(focal_point.x, 0.25);
+/// assert_eq!(focal_point.y, 0.75);
+/// ```
+pub fn parse_focal_point(xmp: &str) -> Option<FocalPoint> {
+    let regions = &xmp[xmp.find("mwg-rs:Regions")?..];
+    let x = get_value(regions, "stArea:x")?;
+    let y = get_value(regions, "stArea:y")?;
+    Some(FocalPoint { x, y })
+}
+
+/// Returns the first value of the given property. Both, the attribute and
+/// the element notation are supported.
+fn get_value(xmp: &str, name: &str) -> Option<f32> {
+    let attribute = format!("{}=\"", name);
+    let element = format!("<{}>", name);
+    let value = if let Some(idx) = xmp.find(&attribute) {
+        let value = &xmp[idx + attribute.len()..];
+        &value[..value.find('"')?]
+    } else {
+        let idx = xmp.find(&element)?;
+        let value = &xmp[idx + element.len()..];
+        &value[..value.find('<')?]
+    };
+    value.trim().parse::<f32>().ok()
+}
+
+/// Removes all properties whose name starts with the given prefix from the
+/// XMP packet. Returns `None` if the prefix occurs outside of a property
+/// name, as the packet cannot be cleaned reliably then.
+/// ## Example
+///
+/// ```
+/// use html5_picture::utils::xmp::remove_properties;
+///
+/// let xmp = r#"<rdf:Description exif:GPSAltitude="30/1">
+///     <exif:GPSLatitude>52,31.2N</exif:GPSLatitude>
+/// </rdf:Description>"#;
+/// let xmp = remove_properties(xmp, "exif:GPS").unwrap();
+/// assert!(!xmp.contains("GPS"));
+/// assert!(xmp.starts_with("<rdf:Description>"));
+/// ```
+pub fn remove_properties(xmp: &str, prefix: &str) -> Option<String> {
+    let mut xmp = xmp.to_string();
+    while let Some(idx) = xmp.find(prefix) {
+        let range = get_property_range(&xmp, idx)?;
+        xmp.replace_range(range, "");
+    }
+    Some(xmp)
+}
+
+/// Creates an XMP packet containing the author and the rights of the given
+/// packet only. Returns `None` if neither of them exists.
+/// ## Example
+///
+/// ```
+/// use html5_picture::utils::xmp::create_rights_packet;
+///
+/// let xmp = r#"<rdf:Description exif:GPSAltitude="30/1"
+///     xmpRights:WebStatement="https://example.com/license">
+///     <dc:rights><rdf:Alt><rdf:li xml:lang="x-default">(c) Jane Doe
+///     </rdf:li></rdf:Alt></dc:rights>
+/// </rdf:Description>"#;
+/// let xmp = create_rights_packet(xmp).unwrap();
+/// assert!(xmp.contains("<dc:rights><rdf:Alt>"));
+/// assert!(xmp.contains(
+///     "<xmpRights:WebStatement>https://example.com/license</xmpRights:"
+/// ));
+/// assert!(!xmp.contains("GPS"));
+/// ```
+pub fn create_rights_packet(xmp: &str) -> Option<String> {
+    let properties: Vec<String> = RIGHTS_PROPERTIES
+        .iter()
+        .filter_map(|name| get_property(xmp, name))
+        .collect();
+    if properties.is_empty() {
+        return None;
+    }
+    Some(format!(
+        concat!(
+            r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">"#,
+            r#"<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">"#,
+            r#"<rdf:Description rdf:about="""#,
+            r#" xmlns:dc="http://purl.org/dc/elements/1.1/""#,
+            r#" xmlns:xmpRights="http://ns.adobe.com/xap/1.0/rights/">"#,
+            "{}</rdf:Description></rdf:RDF></x:xmpmeta>"
+        ),
+        properties.concat()
+    ))
+}
+
+/// Returns the property with the given name in element notation.
+fn get_property(xmp: &str, name: &str) -> Option<String> {
+    xmp.match_indices(name).find_map(|(idx, _)| {
+        if get_name(&xmp[idx..]) != name {
+            return None;
+        }
+        let property = &xmp[get_property_range(xmp, idx)?];
+        match property.starts_with('<') {
+            true => Some(property.to_string()),
+            // the attribute is given as ` name="value"`
+            false => {
+                let value = &property[name.len() + 3..property.len() - 1];
+                Some(format!("<{0}>{1}</{0}>", name, value))
+            }
+        }
+    })
+}
+
+/// Returns the range of the property whose name starts at the given index.
+/// The range includes the tags of the element notation or the leading
+/// whitespace of the attribute notation.
+fn get_property_range(xmp: &str, idx: usize) -> Option<Range<usize>> {
+    let name = get_name(&xmp[idx..]);
+    let rest = &xmp[idx + name.len()..];
+    if xmp[..idx].ends_with('<') {
+        let tag_end = idx + name.len() + rest.find('>')?;
+        if xmp[..tag_end].ends_with('/') {
+            return Some(idx - 1..tag_end + 1);
+        }
+        let closing = format!("</{}>", name);
+        let end = tag_end + xmp[tag_end..].find(&closing)? + closing.len();
+        Some(idx - 1..end)
+    } else if xmp[..idx].ends_with([' ', '\t', '\r', '\n'])
+        && rest.starts_with('=')
+    {
+        let quote = rest[1..].chars().next()?;
+        if quote != '"' && quote != '\'' {
+            return None;
+        }
+        let value = idx + name.len() + 2;
+        let end = value + xmp[value..].find(quote)? + 1;
+        Some(idx - 1..end)
+    } else {
+        None
+    }
+}
+
+/// Returns the qualified name at the start of the given string.
+fn get_name(xmp: &str) -> &str {
+    let end = xmp
+        .find(|c: char| !(c.is_alphanumeric() || ":_-.".contains(c)))
+        .unwrap_or(xmp.len());
+    &xmp[..end]
+}
+
+/// Returns the position of the first occurrence of needle in haystack.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
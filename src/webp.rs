@@ -1,6 +1,12 @@
 const DEFAULT_QUALITY: u8 = 70;
 
+mod animation;
+mod metadata;
 mod parameter;
 pub mod processor;
 
-pub use parameter::WebpParameter;
+pub use {
+    animation::encode_animation,
+    metadata::{WebpMetadata, embed_metadata},
+    parameter::WebpParameter,
+};
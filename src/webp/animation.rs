@@ -0,0 +1,105 @@
+//! Encodes animations to animated webp images.
+
+use {
+    super::metadata::read_chunk_size,
+    crate::utils::animation::Animation,
+    image::metadata::LoopCount,
+    webp::{AnimEncoder, AnimFrame, WebPConfig},
+};
+
+/// Offset of the duration in the payload of an `ANMF` chunk.
+const FRAME_DURATION_OFFSET: usize = 12;
+
+/// Encodes the animation to an animated webp image of the given quality. The
+/// duration of every frame and the loop count are preserved.
+/// ## Example
+///
+/// ```
+/// use {
+///     html5_picture::{
+///         utils::animation::{Animation, AnimationFrame},
+///         webp::encode_animation,
+///     },
+///     image::{DynamicImage, Rgba, RgbaImage, metadata::LoopCount},
+/// };
+///
+/// let frame = |value, duration| AnimationFrame {
+///     image: DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+///         8,
+///         8,
+///         Rgba([value, value, value, 255]),
+///     )),
+///     duration,
+/// };
+/// let animation = Animation {
+///     frames: vec![frame(0, 100), frame(255, 250)],
+///     loop_count: LoopCount::Infinite,
+/// };
+/// let webp = encode_animation(&animation, 70.).unwrap();
+/// assert_eq!(&webp[12..16], b"VP8X");
+/// assert_eq!(&webp[30..34], b"ANIM");
+/// ```
+pub fn encode_animation(
+    animation: &Animation,
+    quality: f32,
+) -> Result<Vec<u8>, String> {
+    let mut config = match WebPConfig::new() {
+        Ok(c) => c,
+        Err(_) => return Err("Could not create webp config!".to_string()),
+    };
+    config.quality = quality;
+    let mut encoder =
+        AnimEncoder::new(animation.width(), animation.height(), &config);
+    encoder.set_loop_count(match animation.loop_count {
+        LoopCount::Infinite => 0,
+        LoopCount::Finite(n) => n.get().min(u16::MAX as u32) as i32,
+    });
+    let mut timestamp = 0;
+    for frame in &animation.frames {
+        match AnimFrame::from_image(&frame.image, timestamp as i32) {
+            Ok(f) => encoder.add_frame(f),
+            Err(msg) => return Err(msg.to_string()),
+        }
+        timestamp += frame.duration;
+    }
+    let mut webp = match encoder.try_encode() {
+        Ok(w) => w.to_vec(),
+        Err(msg) => return Err(format!("{:?}", msg)),
+    };
+    set_last_frame_duration(&mut webp, animation.get_total_duration())?;
+    Ok(webp)
+}
+
+/// Sets the duration of the last frame, so the animation takes the given
+/// total duration. The encoder does not know when the last frame ends and
+/// uses the average duration of the other frames instead.
+fn set_last_frame_duration(
+    webp: &mut [u8],
+    total_duration: u32,
+) -> Result<(), String> {
+    let mut durations = vec![];
+    let mut offset = 12;
+    while offset + 8 <= webp.len() {
+        let size = read_chunk_size(&webp[offset..])?;
+        if &webp[offset..offset + 4] == b"ANMF" {
+            let duration = offset + 8 + FRAME_DURATION_OFFSET;
+            if duration + 3 > webp.len() {
+                return Err("Truncated webp chunk!".to_string());
+            }
+            durations.push(duration);
+        }
+        offset += 8 + size + size % 2;
+    }
+    let (last, others) = match durations.split_last() {
+        Some(d) => d,
+        // identical frames are merged into a still image
+        None => return Ok(()),
+    };
+    let others: u32 = others
+        .iter()
+        .map(|d| u32::from_le_bytes([webp[*d], webp[d + 1], webp[d + 2], 0]))
+        .sum();
+    let duration = total_duration.saturating_sub(others).min(0xffffff);
+    webp[*last..*last + 3].copy_from_slice(&duration.to_le_bytes()[..3]);
+    Ok(())
+}
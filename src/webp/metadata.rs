@@ -0,0 +1,141 @@
+//! Embeds metadata into encoded webp images. The encoder writes the simple
+//! file format, which cannot carry metadata, so it is converted to the
+//! extended format by a `VP8X` chunk. The `ICCP` chunk precedes the image
+//! data, the `EXIF` and `XMP ` chunks follow it.
+
+/// Flag of the `VP8X` chunk signalling an `ICCP` chunk.
+const ICC_FLAG: u8 = 0x20;
+
+/// Flag of the `VP8X` chunk signalling alpha.
+const ALPHA_FLAG: u8 = 0x10;
+
+/// Flag of the `VP8X` chunk signalling an `EXIF` chunk.
+const EXIF_FLAG: u8 = 0x08;
+
+/// Flag of the `VP8X` chunk signalling an `XMP ` chunk.
+const XMP_FLAG: u8 = 0x04;
+
+/// The metadata carried by the webp images.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WebpMetadata {
+    /// The ICC profile of the color space of the image.
+    pub icc_profile: Option<Vec<u8>>,
+    /// The raw EXIF metadata, starting with the TIFF header.
+    pub exif: Option<Vec<u8>>,
+    /// The XMP packet.
+    pub xmp: Option<Vec<u8>>,
+}
+
+impl WebpMetadata {
+    /// Returns true if there is no metadata to embed.
+    pub fn is_empty(&self) -> bool {
+        self.icc_profile.is_none() && self.exif.is_none() && self.xmp.is_none()
+    }
+
+    /// Returns the flags of the `VP8X` chunk signalling the metadata.
+    fn get_flags(&self) -> u8 {
+        let mut flags = 0;
+        if self.icc_profile.is_some() {
+            flags |= ICC_FLAG;
+        }
+        if self.exif.is_some() {
+            flags |= EXIF_FLAG;
+        }
+        if self.xmp.is_some() {
+            flags |= XMP_FLAG;
+        }
+        flags
+    }
+}
+
+/// Inserts the metadata into the given webp image of the given size. The
+/// image is returned unchanged if there is no metadata.
+/// ## Example
+///
+/// ```
+/// use html5_picture::webp::{WebpMetadata, embed_metadata};
+///
+/// let img = image::DynamicImage::new_rgb8(8, 8);
+/// let webp = webp::Encoder::from_image(&img).unwrap().encode(70.);
+/// let metadata = WebpMetadata {
+///     icc_profile: Some(b"profile".to_vec()),
+///     exif: Some(b"II*\0".to_vec()),
+///     xmp: None,
+/// };
+/// let webp = embed_metadata(&webp, &metadata, 8, 8, false).unwrap();
+/// assert_eq!(&webp[12..16], b"VP8X");
+/// assert_eq!(webp[20], 0x28);
+/// assert_eq!(&webp[30..34], b"ICCP");
+/// assert_eq!(&webp[webp.len() - 12..webp.len() - 8], b"EXIF");
+/// ```
+pub fn embed_metadata(
+    webp: &[u8],
+    metadata: &WebpMetadata,
+    width: u32,
+    height: u32,
+    has_alpha: bool,
+) -> Result<Vec<u8>, String> {
+    if webp.len() < 20 || &webp[0..4] != b"RIFF" || &webp[8..12] != b"WEBP" {
+        return Err("Invalid webp image!".to_string());
+    }
+    if metadata.is_empty() {
+        return Ok(webp.to_vec());
+    }
+    let chunks = &webp[12..];
+    let mut content = b"WEBP".to_vec();
+    let image_data = match &chunks[0..4] {
+        b"VP8X" => {
+            // the header already exists, e.g. for lossy images with alpha
+            let size = read_chunk_size(chunks)?;
+            let mut header = chunks[..8 + size].to_vec();
+            header[8] |= metadata.get_flags();
+            content.extend_from_slice(&header);
+            chunks.get(8 + size + size % 2..).unwrap_or_default()
+        }
+        _ => {
+            let mut header = vec![0u8; 10];
+            header[0] = match has_alpha {
+                true => metadata.get_flags() | ALPHA_FLAG,
+                false => metadata.get_flags(),
+            };
+            header[4..7].copy_from_slice(&(width - 1).to_le_bytes()[..3]);
+            header[7..10].copy_from_slice(&(height - 1).to_le_bytes()[..3]);
+            write_chunk(&mut content, b"VP8X", &header);
+            chunks
+        }
+    };
+    if let Some(icc_profile) = &metadata.icc_profile {
+        write_chunk(&mut content, b"ICCP", icc_profile);
+    }
+    content.extend_from_slice(image_data);
+    if let Some(exif) = &metadata.exif {
+        write_chunk(&mut content, b"EXIF", exif);
+    }
+    if let Some(xmp) = &metadata.xmp {
+        write_chunk(&mut content, b"XMP ", xmp);
+    }
+    let mut riff = b"RIFF".to_vec();
+    riff.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    riff.extend_from_slice(&content);
+    Ok(riff)
+}
+
+/// Reads the size of the chunk at the start of the given data.
+pub(crate) fn read_chunk_size(chunk: &[u8]) -> Result<usize, String> {
+    let size =
+        u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as usize;
+    match chunk.len() >= 8 + size {
+        true => Ok(size),
+        false => Err("Truncated webp chunk!".to_string()),
+    }
+}
+
+/// Appends a chunk with the given id and payload, padded to an even size.
+fn write_chunk(data: &mut Vec<u8>, id: &[u8; 4], payload: &[u8]) {
+    data.extend_from_slice(id);
+    data.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    data.extend_from_slice(payload);
+    if payload.len() % 2 == 1 {
+        data.push(0);
+    }
+}
@@ -1,6 +1,15 @@
 //! Contains the processors that execute the single and batch conversion.
 
-use {crate::webp::WebpParameter, std::path::PathBuf};
+use {
+    crate::{
+        utils::{
+            PlaceholderType, color::ColorParameter, derivative::Derivative,
+            metadata::MetadataPolicy, resample::ResizeParameter,
+        },
+        webp::WebpParameter,
+    },
+    std::path::PathBuf,
+};
 
 mod batch;
 mod single;
@@ -16,6 +25,15 @@ pub struct Parameter {
     pub webp_parameter: WebpParameter,
     pub input: PathBuf,
     pub output_dir: PathBuf,
+    pub metadata_dir: PathBuf,
     pub scaled_images_count: u8,
     pub single_threaded: bool,
+    pub placeholder: Option<PlaceholderType>,
+    pub palette_size: Option<u8>,
+    pub derivatives: Vec<Derivative>,
+    pub density_width: Option<u32>,
+    pub resize_parameter: ResizeParameter,
+    pub color_parameter: ColorParameter,
+    pub metadata_policy: MetadataPolicy,
+    pub svg_width: u32,
 }
@@ -5,9 +5,14 @@ use {
     },
     indicatif::MultiProgress,
     log::error,
-    std::{path::PathBuf, sync::Arc}, tokio::task::JoinSet,
+    std::{path::PathBuf, sync::Arc}, tokio::{sync::Semaphore, task::JoinSet},
 };
 
+/// Maximum amount of images that are processed at the same time. Every image
+/// keeps its decoded pixels and their floating point copy in memory, and the
+/// resampling of a single image already uses all cores.
+const MAX_CONCURRENT_IMAGES: usize = 4;
+
 /// Contains all the required and optional parameter for the ```BatchProcessor```.
 /// Currently it is only a wrapper around the single processor parameter.
 pub struct BatchParameter {
@@ -40,6 +45,7 @@ impl BatchProcessor {
         let rt = tokio::runtime::Runtime::new().unwrap();
         rt.block_on(async {
             let mut handles = JoinSet::new();
+            let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_IMAGES));
             for file_name in file_names {
                 let mut params_single = self.params.single_params.clone();
                 params_single.input = file_name.clone();
@@ -49,6 +55,10 @@ impl BatchProcessor {
                     &self.params.single_params.input,
                     &file_name
                     ).unwrap();
+                params_single.metadata_dir = crate::path::create_metadata_file_name(
+                    &self.params.single_params.input,
+                    &file_name
+                    ).unwrap();
 
                 let pb = if let Some(m) = &self.progressbars {
                     let pb =
@@ -59,7 +69,10 @@ impl BatchProcessor {
                 } else {
                     None
                 };
+                let semaphore = Arc::clone(&semaphore);
                 handles.spawn(async move {
+                    // the semaphore is never closed
+                    let _permit = semaphore.acquire_owned().await.unwrap();
                     let result = std::panic::catch_unwind(|| {
                         let mut webp_processor =
                             crate::webp::processor::SingleProcessor::new(
@@ -71,8 +84,12 @@ impl BatchProcessor {
                             error!("Error: {}", msg);
                         };
                     });
-                    if result.is_err() {
-                        pb.unwrap().abandon_with_message("Wrong color profile!");
+                    if let Err(panic) = result {
+                        let msg = get_panic_message(panic.as_ref());
+                        error!("Error: {}", msg);
+                        if let Some(pb) = pb {
+                            pb.abandon_with_message(format!("Error: {}", msg));
+                        }
                     }
                 });
             }
@@ -99,6 +116,10 @@ impl BatchProcessor {
                 &self.params.single_params.input,
                 &file_name
                 ).unwrap();
+            params_single.metadata_dir = crate::path::create_metadata_file_name(
+                &self.params.single_params.input,
+                &file_name
+                ).unwrap();
 
             let mut webp_processor =
                 crate::webp::processor::SingleProcessor::new(
@@ -112,3 +133,14 @@ impl BatchProcessor {
         }
     }
 }
+
+/// Extracts the message of a caught panic.
+fn get_panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+    match panic.downcast_ref::<&str>() {
+        Some(msg) => msg.to_string(),
+        None => match panic.downcast_ref::<String>() {
+            Some(msg) => msg.clone(),
+            None => "Processing the image panicked!".to_string(),
+        },
+    }
+}
@@ -1,11 +1,25 @@
 use {
     super::Parameter,
-    crate::utils::ResizedImageDetails,
-    image::{DynamicImage, ImageReader},
+    crate::utils::{
+        ImageInfo, ImageMetadata, Placeholder, ResizedImageDetails, Sidecar,
+        animation::{self, Animation},
+        color,
+        crop::FocalPoint,
+        imageops, palette,
+        resample::{CASCADE_MIN_RATIO, PreparedImage, ResizeStrategy},
+        svg::Svg,
+        xmp,
+    },
+    crate::webp::{WebpMetadata, embed_metadata, encode_animation},
+    image::{DynamicImage, ImageEncoder, ImageReader, codecs::png::PngEncoder},
     indicatif::ProgressBar,
     log::error,
-    std::{fs::File, io::Write, path::PathBuf},
-    webp::WebPMemory,
+    std::{
+        cell::OnceCell,
+        fs::File,
+        io::{BufWriter, Write},
+        path::PathBuf,
+    },
 };
 
 /// Resizes and converts the given input file to webp format. Every function
@@ -14,6 +28,12 @@ use {
 pub struct SingleProcessor {
     params: Parameter,
     image: Option<DynamicImage>,
+    /// The source of the static versions, created on first use.
+    prepared_image: OnceCell<PreparedImage>,
+    animation: Option<Animation>,
+    svg: Option<Svg>,
+    metadata: WebpMetadata,
+    focal_point: Option<FocalPoint>,
     progressbar: Option<ProgressBar>,
 }
 
@@ -32,34 +52,155 @@ impl SingleProcessor {
         Ok(Self {
             params,
             image: None,
+            prepared_image: OnceCell::new(),
+            animation: None,
+            svg: None,
+            metadata: WebpMetadata::default(),
+            focal_point: None,
             progressbar,
         })
     }
 
-    /// Loads the image from disk.
-    fn load_image(&self) -> Result<DynamicImage, String> {
+    /// Loads the image from disk and rotates it as given by its EXIF
+    /// orientation. The information about the image is read from the same
+    /// decoder, so the file is only opened once.
+    fn load_image(&self) -> Result<(DynamicImage, ImageInfo), String> {
         let img = ImageReader::open(&self.params.input);
         let img = if let Err(msg) = img {
             return Err(msg.to_string());
         } else {
             img.unwrap()
         };
-        let img = match img.decode() {
+        let mut decoder = match img.into_decoder() {
+            Ok(d) => d,
+            Err(msg) => return Err(msg.to_string()),
+        };
+        let info = ImageInfo::from_decoder(&mut decoder)?;
+        let mut img = match DynamicImage::from_decoder(decoder) {
             Ok(i) => i,
             Err(msg) => return Err(msg.to_string()),
         };
-        Ok(img)
+        img.apply_orientation(info.orientation);
+        Ok((img, info))
+    }
+
+    /// Parses the SVG image and rasterizes it at its displayed size. The SVG
+    /// is kept to rasterize the resized images at their size.
+    fn load_svg(&mut self) -> Result<(DynamicImage, ImageInfo), String> {
+        let svg = Svg::from(&self.params.input, self.params.svg_width)?;
+        let info = ImageInfo::from_svg(&svg);
+        let img = svg.rasterize(None, info.width, info.height)?;
+        self.svg = Some(svg);
+        Ok((img, info))
     }
 
-    /// Encodes the image stored internally to webp.
-    fn encode_image(&self, img: &DynamicImage) -> Result<WebPMemory, String> {
-        let encoder = webp::Encoder::from_image(img);
+    /// Encodes the given image to webp, embedding the ICC profile of its
+    /// color space and the kept metadata if required. Pixel formats that are
+    /// not supported by the encoder are converted to 8 bit RGB(A) before.
+    fn encode_image(&self, img: &DynamicImage) -> Result<Vec<u8>, String> {
+        let img = imageops::to_8bit_rgb(img);
+        let encoder = webp::Encoder::from_image(&img);
         if let Err(msg) = &encoder {
             return Err(msg.to_string());
         }
-        Ok(encoder
+        let webp = encoder
             .unwrap()
-            .encode(self.params.webp_parameter.quality as f32))
+            .encode(self.params.webp_parameter.quality as f32);
+        embed_metadata(
+            &webp,
+            &self.metadata,
+            img.width(),
+            img.height(),
+            img.color().has_alpha(),
+        )
+    }
+
+    /// Loads all frames of the image if it is animated. The frames are
+    /// rotated and converted like the image itself.
+    fn load_animation(
+        &self,
+        info: &ImageInfo,
+    ) -> Result<Option<Animation>, String> {
+        let animation = match Animation::from(&self.params.input)? {
+            Some(a) => a,
+            None => return Ok(None),
+        };
+        let color_space = self.params.color_parameter.color_space;
+        let animation = animation.map_images(|mut img| {
+            img.apply_orientation(info.orientation);
+            match &info.icc_profile {
+                Some(profile) => color::convert(img, profile, color_space),
+                None => Ok(img),
+            }
+        })?;
+        Ok(Some(animation))
+    }
+
+    /// Encodes the given animation to an animated webp, embedding the
+    /// metadata like `encode_image`.
+    fn encode_animation(
+        &self,
+        animation: &Animation,
+    ) -> Result<Vec<u8>, String> {
+        let webp = encode_animation(
+            animation,
+            self.params.webp_parameter.quality as f32,
+        )?;
+        embed_metadata(
+            &webp,
+            &self.metadata,
+            animation.width(),
+            animation.height(),
+            true,
+        )
+    }
+
+    /// Saves the first frame of the animated image as static poster, which
+    /// is used as fallback of the `<picture>` tag. The ICC profile of the
+    /// webp images is embedded as well.
+    fn save_poster(&self) -> Result<(), String> {
+        let file_name = animation::get_poster_file_name(&self.params.input)?;
+        let file = match File::create(self.params.output_dir.join(file_name)) {
+            Ok(f) => f,
+            Err(msg) => return Err(msg.to_string()),
+        };
+        let mut encoder = PngEncoder::new(BufWriter::new(file));
+        if let Some(icc_profile) = &self.metadata.icc_profile
+            && let Err(msg) = encoder.set_icc_profile(icc_profile.clone())
+        {
+            return Err(msg.to_string());
+        }
+        // unwrap allowed, the image is loaded before the poster is saved
+        match self.image.as_ref().unwrap().write_with_encoder(encoder) {
+            Ok(_) => Ok(()),
+            Err(msg) => Err(msg.to_string()),
+        }
+    }
+
+    /// Converts the image to the color space given by the parameter, if it
+    /// has an embedded ICC profile.
+    fn convert_color_space(
+        &mut self,
+        img: DynamicImage,
+        info: &ImageInfo,
+    ) -> Result<DynamicImage, String> {
+        let params = &self.params.color_parameter;
+        self.metadata.icc_profile =
+            params.get_output_icc_profile(info.icc_profile.is_some())?;
+        match &info.icc_profile {
+            Some(profile) => color::convert(img, profile, params.color_space),
+            None => Ok(img),
+        }
+    }
+
+    /// Determines the EXIF and XMP metadata kept in the webp images, as
+    /// given by the metadata policy.
+    fn filter_metadata(&mut self, info: &ImageInfo) {
+        let policy = self.params.metadata_policy;
+        self.metadata.exif =
+            info.exif.as_ref().and_then(|exif| policy.filter_exif(exif));
+        self.metadata.xmp =
+            info.xmp.as_ref().and_then(|xmp| policy.filter_xmp(xmp));
     }
 
     /// Loads, resizes and converts the image to webp. Single threaded.
@@ -76,7 +217,15 @@ impl SingleProcessor {
             pb.set_prefix(fname);
             pb.set_message("Loading image...");
         }
-        self.image = Some(self.load_image().unwrap());
+        let (image, mut info) = match crate::is_svg(&self.params.input) {
+            true => self.load_svg()?,
+            false => self.load_image()?,
+        };
+        let image = self.convert_color_space(image, &info)?;
+        self.filter_metadata(&info);
+        self.image = Some(image);
+        self.animation = self.load_animation(&info)?;
+        info.animated = self.animation.is_some();
 
         let output_file_name = match self.get_output_file_name() {
             Ok(v) => v,
@@ -86,7 +235,13 @@ impl SingleProcessor {
         if let Some(pb) = &self.progressbar {
             pb.set_message("Encoding...");
         }
-        let encoded_img = self.encode_image(self.image.as_ref().unwrap())?;
+        let encoded_img = match &self.animation {
+            Some(animation) => {
+                self.save_poster()?;
+                self.encode_animation(animation)?
+            }
+            None => self.encode_image(self.image.as_ref().unwrap())?,
+        };
         if let Some(pb) = &self.progressbar {
             pb.set_message("Saving...");
         }
@@ -100,9 +255,57 @@ impl SingleProcessor {
             pb.set_message("...done!");
             pb.inc(1);
         }
-        match ResizedImageDetails::from(
+        if let Some(pb) = &self.progressbar {
+            pb.set_message("Creating metadata...");
+        }
+        self.run_create_metadata()?;
+        let resized_image_details = match self.params.density_width {
+            Some(css_width) => ResizedImageDetails::from_pixel_density(
+                &self.params.input,
+                &info,
+                css_width,
+                ResizedImageDetails::MAX_PIXEL_DENSITY,
+            ),
+            None => ResizedImageDetails::from(
+                &self.params.input,
+                &info,
+                self.params.scaled_images_count,
+            ),
+        };
+        match resized_image_details {
+            Ok(v) => {
+                self.run_resize_images(v)?;
+            }
+            Err(msg) => {
+                if let Some(pb) = &self.progressbar {
+                    pb.finish_with_message(format!("Error: {}", &msg));
+                }
+                error!("{}", msg);
+                return Err(msg);
+            }
+        };
+        let sidecar = match Sidecar::from(&self.params.input) {
+            Ok(v) => v,
+            Err(msg) => {
+                if let Some(pb) = &self.progressbar {
+                    pb.finish_with_message(format!("Error: {}", &msg));
+                }
+                error!("{}", msg);
+                return Err(msg);
+            }
+        };
+        self.focal_point = match sidecar.focal_point {
+            Some(f) => Some(f),
+            None => info
+                .xmp
+                .as_ref()
+                .and_then(|xmp| std::str::from_utf8(xmp).ok())
+                .and_then(xmp::parse_focal_point),
+        };
+        match ResizedImageDetails::from_art_direction(
             &self.params.input,
-            self.params.scaled_images_count,
+            &info,
+            &sidecar.art_direction,
         ) {
             Ok(v) => {
                 self.run_resize_images(v)?;
@@ -115,6 +318,28 @@ impl SingleProcessor {
                 return Err(msg);
             }
         };
+        for derivative in &self.params.derivatives {
+            match ResizedImageDetails::from_derivative(
+                &self.params.input,
+                &info,
+                derivative,
+            ) {
+                Ok(v) => {
+                    self.run_resize_images(v)?;
+                }
+                Err(msg) => {
+                    if let Some(pb) = &self.progressbar {
+                        pb.finish_with_message(format!("Error: {}", &msg));
+                    }
+                    error!("{}", msg);
+                    return Err(msg);
+                }
+            };
+        }
+        self.prepared_image = OnceCell::new();
+        self.image = None;
+        self.animation = None;
+        self.svg = None;
         if let Some(pb) = &self.progressbar {
             //pb.finish_and_clear();
             pb.finish_with_message("Done!");
@@ -122,11 +347,50 @@ impl SingleProcessor {
         Ok(())
     }
 
-    /// Subroutine of `run`, processes the resizing and conversion.
+    /// Returns true if any of the parameters requires an `ImageMetadata` file.
+    fn is_metadata_required(&self) -> bool {
+        self.params.placeholder.is_some() || self.params.palette_size.is_some()
+    }
+
+    /// Subroutine of `run`, determines the image metadata and saves it to the
+    /// metadata directory. A metadata file of a previous run is removed if
+    /// no metadata is required anymore, so it is not applied to the tags.
+    fn run_create_metadata(&self) -> Result<(), String> {
+        let file_name = match self.params.input.file_name() {
+            Some(f) => self.params.metadata_dir.join(f),
+            None => return Err("File name could not be extracted!".to_string()),
+        };
+        if !self.is_metadata_required() {
+            return ImageMetadata::remove(&file_name);
+        }
+        let img = self.image.as_ref().unwrap();
+        let mut metadata = ImageMetadata::default();
+        if let Some(placeholder_type) = self.params.placeholder {
+            metadata.placeholder =
+                Some(Placeholder::from(img, placeholder_type)?);
+        }
+        if let Some(palette_size) = self.params.palette_size {
+            metadata.palette = palette::extract_palette(img, palette_size)
+                .iter()
+                .map(palette::to_hex)
+                .collect();
+            metadata.dominant_color = metadata.palette.first().cloned();
+        }
+        metadata.save(&file_name)
+    }
+
+    /// Subroutine of `run`, processes the resizing and conversion. The
+    /// details are processed from the largest to the smallest, so the cascade
+    /// strategy can derive the versions from the previous one. SVGs are
+    /// rasterized at the size of each version instead.
     fn run_resize_images(
         &self,
         details: Vec<ResizedImageDetails>,
     ) -> Result<(), String> {
+        let img = self.image.as_ref().unwrap();
+        let cascade =
+            self.params.resize_parameter.strategy == ResizeStrategy::Cascade;
+        let mut previous = None;
         for detail in details.iter().rev() {
             if let Some(pb) = &self.progressbar {
                 pb.set_message(format!(
@@ -134,32 +398,93 @@ impl SingleProcessor {
                     &detail.width, &detail.height
                 ));
             }
-            let img = self.image.as_ref().unwrap().resize(
-                detail.width,
-                detail.height,
-                image::imageops::FilterType::Triangle,
-            );
-            let output_file_name =
-                &self.params.output_dir.join(&detail.output_file_name);
-            if let Some(pb) = &self.progressbar {
-                pb.set_message("Encoding...");
+            let rect = detail.crop.as_ref().map(|crop| {
+                imageops::get_crop_rect(img, crop, self.focal_point.as_ref())
+            });
+            if let Some(svg) = &self.svg {
+                let img =
+                    svg.rasterize(rect.as_ref(), detail.width, detail.height)?;
+                if let Some(pb) = &self.progressbar {
+                    pb.set_message("Encoding...");
+                }
+                let encoded = self.encode_image(&img)?;
+                self.save_resized_image(detail, &encoded);
+                continue;
             }
-            let img = &self.encode_image(&img)?;
-            if let Some(pb) = &self.progressbar {
-                pb.set_message("Saving...");
+            if let Some(animation) = &self.animation {
+                let resized = animation.crop_and_resize(
+                    rect.as_ref(),
+                    detail.width,
+                    detail.height,
+                    &self.params.resize_parameter,
+                );
+                if let Some(pb) = &self.progressbar {
+                    pb.set_message("Encoding...");
+                }
+                let encoded = self.encode_animation(&resized)?;
+                self.save_resized_image(detail, &encoded);
+                continue;
             }
-            let mut buf = File::create(output_file_name).unwrap();
-            if let Err(msg) = buf.write_all(&img) {
-                error!("{}", msg);
+            let resized = match &previous {
+                // only versions of the same crop can be derived from each
+                // other
+                Some((previous_rect, previous))
+                    if cascade
+                        && *previous_rect == rect
+                        && is_cascade_source(previous, detail) =>
+                {
+                    previous.resize(detail.width, detail.height)
+                }
+                _ => {
+                    // animations and SVGs never get here, so their first
+                    // frame is not prepared needlessly
+                    let prepared_image =
+                        self.prepared_image.get_or_init(|| {
+                            PreparedImage::new(
+                                img,
+                                &self.params.resize_parameter,
+                            )
+                        });
+                    match &rect {
+                        Some(rect) => prepared_image
+                            .crop(rect)
+                            .resize(detail.width, detail.height),
+                        None => {
+                            prepared_image.resize(detail.width, detail.height)
+                        }
+                    }
+                }
             };
+            let img = resized.to_image();
+            if cascade {
+                previous = Some((rect, resized));
+            }
             if let Some(pb) = &self.progressbar {
-                pb.set_message("...done!");
-                pb.inc(1);
+                pb.set_message("Encoding...");
             }
+            let img = &self.encode_image(&img)?;
+            self.save_resized_image(detail, img);
         }
         Ok(())
     }
 
+    /// Saves the encoded version of the image described by the details.
+    fn save_resized_image(&self, detail: &ResizedImageDetails, encoded: &[u8]) {
+        let output_file_name =
+            &self.params.output_dir.join(&detail.output_file_name);
+        if let Some(pb) = &self.progressbar {
+            pb.set_message("Saving...");
+        }
+        let mut buf = File::create(output_file_name).unwrap();
+        if let Err(msg) = buf.write_all(encoded) {
+            error!("{}", msg);
+        };
+        if let Some(pb) = &self.progressbar {
+            pb.set_message("...done!");
+            pb.inc(1);
+        }
+    }
+
     /// Generates the output file name for resized images.
     fn get_output_file_name(&self) -> Result<PathBuf, String> {
         let file_name = self.params.input.file_stem();
@@ -173,3 +498,13 @@ impl SingleProcessor {
         )))
     }
 }
+
+/// Returns true if the given version is large enough to be the source of the
+/// version described by the details.
+fn is_cascade_source(
+    previous: &PreparedImage,
+    details: &ResizedImageDetails,
+) -> bool {
+    previous.width() >= details.width * CASCADE_MIN_RATIO
+        && previous.height() >= details.height * CASCADE_MIN_RATIO
+}
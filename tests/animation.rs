@@ -0,0 +1,67 @@
+mod common;
+
+use {
+    clap::Parser,
+    html5_picture::{core::Config, utils::ImageInfo},
+    image::{ImageDecoder, codecs::png::PngDecoder},
+    std::{fs::File, io::BufReader},
+};
+
+#[test]
+fn installed_posters_are_no_source_images() {
+    let dir = common::create_test_dir("animation", &["banner.gif", "apng.png"]);
+    let assets = dir.join("assets");
+    // installs into the input directory like the mdBook preprocessor
+    for _ in 0..2 {
+        html5_picture::run(Config::parse_from([
+            "html5-picture".as_ref(),
+            assets.as_os_str(),
+            "1".as_ref(),
+            "-i".as_ref(),
+            assets.as_os_str(),
+        ]));
+    }
+    assert!(assets.join("banner-poster.png").is_file());
+    assert!(assets.join("apng-poster.png").is_file());
+    assert!(!assets.join("banner-poster-poster.png").exists());
+    assert!(!assets.join("banner-poster.webp").exists());
+
+    let mut file_names = html5_picture::collect_image_file_names(&assets, None);
+    file_names.sort();
+    assert_eq!(
+        file_names,
+        vec![assets.join("apng.png"), assets.join("banner.gif")]
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn image_info_tells_if_animated() {
+    let fixtures =
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    for (fixture, animated) in
+        [("banner.gif", true), ("apng.png", true), ("gps.png", false)]
+    {
+        let info = ImageInfo::from(&fixtures.join(fixture)).unwrap();
+        assert_eq!(info.animated, animated, "{}", fixture);
+    }
+}
+
+#[test]
+fn posters_have_the_icc_profile_of_the_webp_images() {
+    let dir = common::create_test_dir("poster-icc", &["banner.gif"]);
+    let install_dir = dir.join("dist");
+    html5_picture::run(Config::parse_from([
+        "html5-picture".as_ref(),
+        dir.join("assets").as_os_str(),
+        "1".as_ref(),
+        "-i".as_ref(),
+        install_dir.as_os_str(),
+        "--embed-icc-profile".as_ref(),
+    ]));
+    let poster = install_dir.join("banner-poster.png");
+    let file = BufReader::new(File::open(&poster).unwrap());
+    let mut decoder = PngDecoder::new(file).unwrap();
+    assert!(decoder.icc_profile().unwrap().is_some());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use {
+    clap::Parser,
+    html5_picture::{core::Config, html5::PictureRegister},
+};
+
+#[test]
+fn cropped_images_are_skipped() {
+    let dir = common::create_test_dir("cdn", &["rgb16.png"]);
+    std::fs::write(
+        dir.join("assets/rgb16.picture.json"),
+        r#"{ "art_direction": [{ "max_width": 20, "crop": { "aspect_ratio": "1:1" } }] }"#,
+    )
+    .unwrap();
+    let tags_dir = dir.join("tags");
+    let manifest = dir.join("pictures.json");
+    let config = Config::parse_from([
+        "html5-picture".as_ref(),
+        dir.join("assets").as_os_str(),
+        "1".as_ref(),
+        "-p".as_ref(),
+        tags_dir.as_os_str(),
+        "-m".as_ref(),
+        "s3://bucket/assets".as_ref(),
+        "--manifest".as_ref(),
+        manifest.as_os_str(),
+        "--derivative".as_ref(),
+        "thumb: 1:1 @ 16".as_ref(),
+        "--cdn-url".as_ref(),
+        "https://img.example.com/{signature}/rs:fit:{width}/plain/{source_url}@webp"
+            .as_ref(),
+    ]);
+    html5_picture::run(config.clone());
+
+    let tag = std::fs::read_to_string(tags_dir.join("rgb16.html")).unwrap();
+    assert!(tag.contains(
+        r#"srcset="https://img.example.com/insecure/rs:fit:32/plain/s3://bucket/assets/rgb16.png@webp""#
+    ));
+    assert!(!tag.contains("max-width: 20px"));
+    assert!(!tags_dir.join("rgb16-thumb.html").exists());
+    assert!(manifest.is_file());
+
+    let register = PictureRegister::from(&config).unwrap();
+    assert_eq!(register.get("rgb16.png").unwrap().sources.len(), 2);
+    assert!(register.get_derivative("rgb16.png", "thumb").is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
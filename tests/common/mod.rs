@@ -0,0 +1,17 @@
+use std::path::PathBuf;
+
+/// Creates an empty directory for the test containing the given fixtures in
+/// its `assets` subdirectory.
+pub fn create_test_dir(name: &str, fixtures: &[&str]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("html5-picture-{}", name));
+    let _ = std::fs::remove_dir_all(&dir);
+    let assets = dir.join("assets");
+    std::fs::create_dir_all(&assets).unwrap();
+    for fixture in fixtures {
+        let source = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(fixture);
+        std::fs::copy(source, assets.join(fixture)).unwrap();
+    }
+    dir
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use {clap::Parser, html5_picture::core::Config};
+
+#[test]
+fn density_versions_are_combined_into_one_srcset() {
+    let dir = common::create_test_dir("density", &["rgb16.png"]);
+    let install_dir = dir.join("dist");
+    let tags_dir = dir.join("tags");
+    let config = Config::parse_from([
+        "html5-picture".as_ref(),
+        dir.join("assets").as_os_str(),
+        "1".as_ref(),
+        "-i".as_ref(),
+        install_dir.as_os_str(),
+        "-p".as_ref(),
+        tags_dir.as_os_str(),
+        "-m".as_ref(),
+        "/assets".as_ref(),
+        "--density-width".as_ref(),
+        "24".as_ref(),
+    ]);
+    html5_picture::run(config);
+
+    // the 3x version would exceed the width of the source image
+    assert!(install_dir.join("rgb16-1x.webp").is_file());
+    assert!(install_dir.join("rgb16-2x.webp").is_file());
+    assert!(!install_dir.join("rgb16-3x.webp").exists());
+    let tag = std::fs::read_to_string(tags_dir.join("rgb16.html")).unwrap();
+    assert!(
+        tag.contains(r#"<source srcset="/assets/rgb16-1x.webp 1x, /assets/rgb16-2x.webp 2x">"#),
+        "{}",
+        tag
+    );
+    assert_eq!(tag.matches("<source").count(), 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
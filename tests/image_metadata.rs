@@ -0,0 +1,125 @@
+mod common;
+
+use {
+    clap::Parser,
+    html5_picture::{core::Config, html5::PictureRegister},
+    std::ffi::OsString,
+    walkdir::WalkDir,
+};
+
+#[test]
+fn metadata_is_not_installed() {
+    let dir = common::create_test_dir("image-metadata", &["gps.png"]);
+    let install_dir = dir.join("dist");
+    let config = Config::parse_from([
+        "html5-picture".as_ref(),
+        dir.join("assets").as_os_str(),
+        "1".as_ref(),
+        "-i".as_ref(),
+        install_dir.as_os_str(),
+        "--placeholder".as_ref(),
+        "blurhash".as_ref(),
+        "--palette".as_ref(),
+        "3".as_ref(),
+    ]);
+    html5_picture::run(config.clone());
+
+    assert!(install_dir.join("gps.webp").is_file());
+    assert!(
+        !WalkDir::new(&install_dir)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .any(|e| e.path().to_string_lossy().ends_with(".json"))
+    );
+    let register = PictureRegister::from(&config).unwrap();
+    let picture = register.get("gps.png").unwrap();
+    assert!(picture.placeholder.is_some());
+    assert!(picture.dominant_color.is_some());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn metadata_of_a_previous_run_is_removed() {
+    let dir = common::create_test_dir("stale-metadata", &["gps.png"]);
+    let install_dir = dir.join("dist");
+    let tags_dir = dir.join("tags");
+    let parse = |options: &[&str]| {
+        let mut args: Vec<OsString> = vec![
+            "html5-picture".into(),
+            dir.join("assets").into(),
+            "1".into(),
+            "-i".into(),
+            install_dir.clone().into(),
+            "-p".into(),
+            tags_dir.clone().into(),
+            "--force-overwrite".into(),
+        ];
+        args.extend(options.iter().map(OsString::from));
+        Config::parse_from(args)
+    };
+    html5_picture::run(parse(&["--placeholder", "blurhash"]));
+    let tag = std::fs::read_to_string(tags_dir.join("gps.html")).unwrap();
+    assert!(tag.contains("data-blurhash"));
+
+    let config = parse(&[]);
+    html5_picture::run(config.clone());
+    let tag = std::fs::read_to_string(tags_dir.join("gps.html")).unwrap();
+    assert!(!tag.contains("data-blurhash"));
+    let register = PictureRegister::from(&config).unwrap();
+    assert!(register.get("gps.png").unwrap().placeholder.is_none());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn palette_is_only_used_as_background_if_requested() {
+    let dir = common::create_test_dir("palette-background", &["gps.png"]);
+    let tags_dir = dir.join("tags");
+    let parse = |options: &[&str]| {
+        let mut args: Vec<OsString> = vec![
+            "html5-picture".into(),
+            dir.join("assets").into(),
+            "1".into(),
+            "-i".into(),
+            dir.join("dist").into(),
+            "-p".into(),
+            tags_dir.clone().into(),
+            "--force-overwrite".into(),
+            "--palette".into(),
+            "2".into(),
+        ];
+        args.extend(options.iter().map(OsString::from));
+        Config::parse_from(args)
+    };
+    let config = parse(&[]);
+    html5_picture::run(config.clone());
+    let tag = std::fs::read_to_string(tags_dir.join("gps.html")).unwrap();
+    let register = PictureRegister::from(&config).unwrap();
+    let picture = register.get("gps.png").unwrap();
+    let dominant_color = picture.dominant_color.clone().unwrap();
+    assert!(tag.contains(&format!("data-dominant-color=\"{dominant_color}\"")));
+    assert!(
+        tag.contains(&format!(
+            "data-palette=\"{}\"",
+            picture.palette.join(" ")
+        ))
+    );
+    assert!(!tag.contains("background-color"));
+    assert!(
+        !picture
+            .to_html_string(None, "")
+            .contains("background-color")
+    );
+
+    let config = parse(&["--palette-background"]);
+    html5_picture::run(config.clone());
+    let tag = std::fs::read_to_string(tags_dir.join("gps.html")).unwrap();
+    assert!(tag.contains(&format!("background-color:{dominant_color}")));
+    let register = PictureRegister::from(&config).unwrap();
+    let picture = register.get("gps.png").unwrap();
+    assert!(
+        picture
+            .to_html_string(None, "")
+            .contains("background-color")
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use {
+    html5_picture::mdbook::{run_preprocessor, supports_renderer},
+    serde_json::{Value, json},
+    std::path::Path,
+};
+
+/// Creates the JSON mdBook passes to the preprocessor for a book having a
+/// chapter and a sub chapter in a subfolder.
+fn create_input(root: &Path) -> String {
+    let chapter = |name: &str, path: &str, content: &str, sub_items| {
+        json!({
+            "Chapter": {
+                "name": name,
+                "content": content,
+                "number": null,
+                "sub_items": sub_items,
+                "path": path,
+                "source_path": path,
+                "parent_names": []
+            }
+        })
+    };
+    let sub_chapter =
+        chapter("Sub", "sub/page.md", "![B](../images/rgb16.png)", json!([]));
+    let context = json!({
+        "root": root,
+        "config": {
+            "book": { "src": "src" },
+            "preprocessor": {
+                "html5-picture": {
+                    "assets": "images",
+                    "scaled-images-count": 2
+                }
+            }
+        },
+        "renderer": "html",
+        "mdbook_version": "0.4.40"
+    });
+    let book = json!({
+        "sections": [chapter(
+            "Intro",
+            "intro.md",
+            "# Intro\n\n![A](images/rgb16.png)\n",
+            json!([sub_chapter])
+        )],
+        "__non_exhaustive": null
+    });
+    json!([context, book]).to_string()
+}
+
+/// Moves the fixtures of the test dir into the images folder of a book.
+fn create_book(name: &str) -> std::path::PathBuf {
+    let dir = common::create_test_dir(name, &["rgb16.png"]);
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    std::fs::rename(dir.join("assets"), dir.join("src/images")).unwrap();
+    dir
+}
+
+#[test]
+fn chapters_are_rewritten() {
+    let dir = create_book("mdbook");
+    let output = run_preprocessor(&create_input(&dir)).unwrap();
+    let book: Value = serde_json::from_str(&output).unwrap();
+
+    let chapter = &book["sections"][0]["Chapter"];
+    let content = chapter["content"].as_str().unwrap();
+    assert!(content.starts_with("# Intro\n\n<picture>"), "{}", content);
+    assert!(content.contains(r#"srcset="images/rgb16-w22.webp""#));
+    assert!(content.contains(r#"alt="A""#));
+    let sub_chapter = &chapter["sub_items"][0]["Chapter"];
+    let content = sub_chapter["content"].as_str().unwrap();
+    assert!(content.contains(r#"srcset="../images/rgb16-w22.webp""#));
+    assert!(dir.join("src/images/rgb16-w43.webp").is_file());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn outdated_images_are_converted_again() {
+    let dir = create_book("mdbook-outdated");
+    let images = dir.join("src/images");
+    run_preprocessor(&create_input(&dir)).unwrap();
+
+    // a missing scaled image is created again
+    std::fs::remove_file(images.join("rgb16-w43.webp")).unwrap();
+    run_preprocessor(&create_input(&dir)).unwrap();
+    assert!(images.join("rgb16-w43.webp").is_file());
+
+    // a changed sidecar file adds the art directed crop
+    std::fs::write(
+        images.join("rgb16.picture.json"),
+        r#"{ "art_direction": [{ "max_width": 20, "crop": { "aspect_ratio": "1:1" } }] }"#,
+    )
+    .unwrap();
+    let output = run_preprocessor(&create_input(&dir)).unwrap();
+    assert!(images.join("rgb16-art20.webp").is_file());
+    assert!(output.contains("rgb16-art20.webp"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn only_the_html_renderer_is_supported() {
+    assert!(supports_renderer("html"));
+    assert!(!supports_renderer("markdown"));
+    assert!(!supports_renderer("epub"));
+
+    let supports = |renderer: &str| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_mdbook-html5-picture"))
+            .args(["supports", renderer])
+            .status()
+            .unwrap()
+            .success()
+    };
+    assert!(supports("html"));
+    assert!(!supports("markdown"));
+}
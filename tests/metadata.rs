@@ -0,0 +1,41 @@
+mod common;
+
+use {
+    clap::Parser,
+    html5_picture::core::Config,
+    image::{ImageDecoder, codecs::png::PngDecoder, metadata::Orientation},
+    std::{fs::File, io::BufReader},
+};
+
+#[test]
+fn installed_original_has_no_gps_ifd() {
+    let dir = common::create_test_dir("metadata", &["gps.png"]);
+    for policy in ["strip", "copyright", "keep-except-gps"] {
+        let install_dir = dir.join(policy);
+        html5_picture::run(Config::parse_from([
+            "html5-picture".as_ref(),
+            dir.join("assets").as_os_str(),
+            "1".as_ref(),
+            "-i".as_ref(),
+            install_dir.as_os_str(),
+            "--metadata".as_ref(),
+            policy.as_ref(),
+        ]));
+
+        let original = install_dir.join("gps.png");
+        let file = BufReader::new(File::open(&original).unwrap());
+        let mut decoder = PngDecoder::new(file).unwrap();
+        let exif = decoder.exif_metadata().unwrap().unwrap();
+        // the pixels of the original are not rotated
+        assert_eq!(
+            Orientation::from_exif_chunk(&exif),
+            Some(Orientation::Rotate90)
+        );
+        // little endian tag of the GPS IFD pointer
+        assert!(!exif.windows(2).any(|w| w == [0x25, 0x88]));
+        let png = std::fs::read(&original).unwrap();
+        assert!(!png.windows(3).any(|w| w == b"GPS"));
+        assert_eq!(image::open(&original).unwrap().to_rgb8().width(), 40);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,151 @@
+#![cfg(feature = "tower")]
+
+mod common;
+
+use {
+    bytes::Bytes,
+    clap::Parser,
+    html5_picture::{
+        core::Config, html5::PictureRegister,
+        middleware::PictureNegotiationLayer,
+    },
+    http::{Method, Request, Response, header},
+    http_body_util::{BodyExt, Full},
+    std::{
+        convert::Infallible,
+        future::{Ready, ready},
+        task::{Context, Poll},
+    },
+    tower_layer::Layer,
+    tower_service::Service,
+};
+
+/// Serves the original image for every request.
+#[derive(Clone)]
+struct Original;
+
+impl Service<Request<()>> for Original {
+    type Response = Response<Full<Bytes>>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        _: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _: Request<()>) -> Self::Future {
+        ready(Ok(Response::new(Full::new(Bytes::from_static(
+            b"original",
+        )))))
+    }
+}
+
+/// Sends a request with the given headers through the layer and returns the
+/// content type, the content length and the body of the response.
+fn send(
+    layer: &PictureNegotiationLayer,
+    method: Method,
+    uri: &str,
+    headers: &[(&str, &str)],
+) -> (Option<String>, Option<u64>, Bytes) {
+    let mut request = Request::builder().method(method).uri(uri);
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    let request = request.body(()).unwrap();
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+        let response = layer.layer(Original).call(request).await.unwrap();
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .map(|v| v.to_str().unwrap().to_string())
+        };
+        let content_type = header(header::CONTENT_TYPE);
+        let content_length =
+            header(header::CONTENT_LENGTH).map(|v| v.parse().unwrap());
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (content_type, content_length, body)
+    })
+}
+
+#[test]
+fn negotiates_format_and_width() {
+    let dir = common::create_test_dir("middleware", &["gps.png"]);
+    let assets = dir.join("assets");
+    std::fs::rename(assets.join("gps.png"), assets.join("gps photo.png"))
+        .unwrap();
+    let install_dir = dir.join("dist");
+    let manifest = dir.join("pictures.json");
+    html5_picture::run(Config::parse_from([
+        "html5-picture".as_ref(),
+        assets.as_os_str(),
+        "2".as_ref(),
+        "-i".as_ref(),
+        install_dir.as_os_str(),
+        "-m".as_ref(),
+        "/assets".as_ref(),
+        "--manifest".as_ref(),
+        manifest.as_os_str(),
+    ]));
+    let register = PictureRegister::from_manifest(&manifest).unwrap();
+    let layer = PictureNegotiationLayer::new(register, install_dir.clone())
+        .with_client_hints(true);
+    let read = |file_name: &str| {
+        Bytes::from(std::fs::read(install_dir.join(file_name)).unwrap())
+    };
+    let webp = Some("image/webp".to_string());
+    let uri = "/assets/gps%20photo.png";
+
+    let (content_type, _, body) = send(
+        &layer,
+        Method::GET,
+        uri,
+        &[("accept", "image/avif;q=0, image/webp;q=0.8, */*")],
+    );
+    assert_eq!((content_type, body), (webp.clone(), read("gps photo.webp")));
+
+    // wildcards and refused types do not count as support
+    for accept in ["*/*", "image/*", "image/webp;q=0", "image/webpx"] {
+        let (_, _, body) =
+            send(&layer, Method::GET, uri, &[("accept", accept)]);
+        assert_eq!(body, "original", "{}", accept);
+    }
+
+    // the smallest scaled version that is at least as wide
+    for (hint, value, file_name) in [
+        ("sec-ch-width", "10", "gps photo-w14.webp"),
+        ("width", "6.5", "gps photo-w7.webp"),
+        ("sec-ch-width", "100", "gps photo.webp"),
+        ("sec-ch-width", "0", "gps photo.webp"),
+    ] {
+        let (_, _, body) = send(
+            &layer,
+            Method::GET,
+            uri,
+            &[("accept", "image/webp"), (hint, value)],
+        );
+        assert_eq!(body, read(file_name), "{}: {}", hint, value);
+    }
+
+    let (content_type, content_length, body) =
+        send(&layer, Method::HEAD, uri, &[("accept", "image/webp")]);
+    assert_eq!(content_type, webp);
+    assert_eq!(content_length, Some(read("gps photo.webp").len() as u64));
+    assert!(body.is_empty());
+
+    // paths leaving the mountpoint are passed to the wrapped service
+    for uri in [
+        "/assets/../assets/gps%20photo.png",
+        "/assets/%2e%2e/gps.png",
+    ] {
+        let (_, _, body) =
+            send(&layer, Method::GET, uri, &[("accept", "image/webp")]);
+        assert_eq!(body, "original", "{}", uri);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
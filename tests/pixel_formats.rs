@@ -0,0 +1,73 @@
+mod common;
+
+use html5_picture::{
+    utils::{
+        color::ColorParameter, metadata::MetadataPolicy,
+        resample::ResizeParameter, svg::DEFAULT_SVG_WIDTH,
+    },
+    webp::{
+        WebpParameter,
+        processor::{Parameter, SingleProcessor},
+    },
+};
+
+/// Fixtures of every PNG color type and bit depth.
+const FIXTURES: [&str; 8] = [
+    "l8.png",
+    "la8.png",
+    "l16.png",
+    "la16.png",
+    "rgb16.png",
+    "rgba16.png",
+    "palette.png",
+    "palette_trns.png",
+];
+
+#[test]
+fn all_pixel_formats_are_converted() {
+    let dir = common::create_test_dir("pixel-formats", &FIXTURES);
+    for fixture in FIXTURES {
+        let input = dir.join("assets").join(fixture);
+        let output_dir = dir.join(fixture.replace('.', "-"));
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let params = Parameter {
+            webp_parameter: WebpParameter::new(Some(90)),
+            input: input.clone(),
+            output_dir: output_dir.clone(),
+            metadata_dir: output_dir.clone(),
+            scaled_images_count: 1,
+            single_threaded: true,
+            placeholder: None,
+            palette_size: None,
+            derivatives: vec![],
+            density_width: None,
+            resize_parameter: ResizeParameter::default(),
+            color_parameter: ColorParameter::default(),
+            metadata_policy: MetadataPolicy::Strip,
+            svg_width: DEFAULT_SVG_WIDTH,
+        };
+        SingleProcessor::new(params, None).unwrap().run().unwrap();
+
+        let source = image::open(&input).unwrap();
+        let webp_file_name = output_dir.join(fixture.replace(".png", ".webp"));
+        let webp = image::open(&webp_file_name).unwrap();
+        assert_eq!(webp.width(), source.width(), "{}", fixture);
+        assert_eq!(webp.height(), source.height(), "{}", fixture);
+        assert_eq!(
+            webp.color().has_alpha(),
+            source.color().has_alpha(),
+            "{}",
+            fixture
+        );
+        // the resized versions are decodable as well
+        let versions: Vec<_> = std::fs::read_dir(&output_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert!(versions.len() >= 2, "{}", fixture);
+        for version in versions {
+            assert!(image::open(&version).is_ok(), "{}", version.display());
+        }
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
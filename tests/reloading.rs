@@ -0,0 +1,85 @@
+mod common;
+
+use {
+    clap::Parser,
+    html5_picture::{
+        core::Config,
+        html5::{RegisterSource, ReloadingPictureRegister},
+        path::get_metadata_dir,
+        utils::ImageMetadata,
+    },
+    std::path::Path,
+};
+
+/// Converts the images of the test dir into `dist`, writing a manifest.
+fn convert(dir: &Path) -> Config {
+    let config = Config::parse_from([
+        "html5-picture".as_ref(),
+        dir.join("assets").as_os_str(),
+        "1".as_ref(),
+        "-i".as_ref(),
+        dir.join("dist").as_os_str(),
+        "--manifest".as_ref(),
+        dir.join("pictures.json").as_os_str(),
+    ]);
+    html5_picture::run(config.clone());
+    config
+}
+
+#[test]
+fn changed_manifest_is_reloaded() {
+    let dir = common::create_test_dir("reloading-manifest", &["rgb16.png"]);
+    convert(&dir);
+    let register = ReloadingPictureRegister::new(RegisterSource::Manifest(
+        dir.join("pictures.json"),
+    ))
+    .unwrap();
+    let before = register.load();
+    assert!(before.get("rgb16.png").is_ok());
+    assert!(before.get("palette.png").is_err());
+    assert!(!register.reload_if_changed().unwrap());
+
+    std::fs::copy(
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/palette.png"),
+        dir.join("assets/palette.png"),
+    )
+    .unwrap();
+    convert(&dir);
+    assert!(register.reload_if_changed().unwrap());
+    assert!(register.load().get("palette.png").is_ok());
+    // snapshots taken before the reload stay unchanged
+    assert!(before.get("palette.png").is_err());
+    assert!(!register.reload_if_changed().unwrap());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn changed_metadata_is_reloaded() {
+    let dir = common::create_test_dir("reloading-metadata", &["rgb16.png"]);
+    let config = convert(&dir);
+    let register = ReloadingPictureRegister::new(RegisterSource::Config(
+        Box::new(config.clone()),
+    ))
+    .unwrap();
+    assert!(
+        register
+            .load()
+            .get("rgb16.png")
+            .unwrap()
+            .dominant_color
+            .is_none()
+    );
+
+    let metadata = ImageMetadata {
+        dominant_color: Some("#102030".to_string()),
+        ..Default::default()
+    };
+    let metadata_dir = get_metadata_dir(&config.input_dir).unwrap();
+    metadata.save(&metadata_dir.join("rgb16.png")).unwrap();
+    assert!(register.reload_if_changed().unwrap());
+    let register = register.load();
+    let picture = register.get("rgb16.png").unwrap();
+    assert_eq!(picture.dominant_color.as_deref(), Some("#102030"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,55 @@
+use {
+    html5_picture::utils::resample::{ResizeParameter, resize},
+    image::{DynamicImage, Rgb, RgbImage, Rgba, RgbaImage},
+};
+
+#[test]
+fn checkerboard_is_averaged_in_linear_light() {
+    let checkerboard = RgbImage::from_fn(64, 64, |x, y| match (x + y) % 2 {
+        0 => Rgb([0, 0, 0]),
+        _ => Rgb([255, 255, 255]),
+    });
+    let img = DynamicImage::ImageRgb8(checkerboard);
+
+    // half of the light is sRGB 188, not 128
+    let resized = resize(&img, 8, 8, &ResizeParameter::default()).to_rgb8();
+    for pixel in resized.pixels() {
+        for c in pixel.0 {
+            assert!((186..=190).contains(&c), "{:?}", pixel);
+        }
+    }
+
+    let params = ResizeParameter {
+        linear_light: false,
+        ..Default::default()
+    };
+    let resized = resize(&img, 8, 8, &params).to_rgb8();
+    for pixel in resized.pixels() {
+        for c in pixel.0 {
+            assert!((126..=130).contains(&c), "{:?}", pixel);
+        }
+    }
+}
+
+#[test]
+fn transparent_pixels_do_not_darken_the_edge() {
+    // a semi-transparent white area next to fully transparent black pixels
+    let edge = RgbaImage::from_fn(64, 64, |x, _| match x < 32 {
+        true => Rgba([255, 255, 255, 128]),
+        false => Rgba([0, 0, 0, 0]),
+    });
+    let img = DynamicImage::ImageRgba8(edge);
+    let resized = resize(&img, 12, 12, &ResizeParameter::default()).to_rgba8();
+    let mut edge_pixels = 0;
+    for pixel in resized.pixels() {
+        let [r, g, b, a] = pixel.0;
+        if a == 0 {
+            continue;
+        }
+        if a < 128 {
+            edge_pixels += 1;
+        }
+        assert!(r >= 250 && g >= 250 && b >= 250, "{:?}", pixel);
+    }
+    assert!(edge_pixels > 0);
+}
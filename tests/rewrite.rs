@@ -0,0 +1,105 @@
+mod common;
+
+use {
+    clap::Parser,
+    html5_picture::{
+        core::Config, html5::PictureRegister, rewrite::PictureResolver,
+    },
+    std::path::Path,
+};
+
+/// Converts the fixture into `dist/assets`, served below `/assets`.
+fn convert(dir: &Path, options: &[&str]) -> Config {
+    let mut args = vec![
+        "html5-picture".to_string(),
+        dir.join("assets").display().to_string(),
+        "2".to_string(),
+        "-i".to_string(),
+        dir.join("dist/assets").display().to_string(),
+        "-m".to_string(),
+        "/assets".to_string(),
+    ];
+    args.extend(options.iter().map(|o| o.to_string()));
+    let config = Config::parse_from(args);
+    html5_picture::run(config.clone());
+    config
+}
+
+#[test]
+fn references_are_resolved_relative_to_the_document() {
+    let dir = common::create_test_dir("resolve", &["rgb16.png"]);
+    let config = convert(&dir, &[]);
+    let register = PictureRegister::from(&config).unwrap();
+    let resolver = PictureResolver::new(&register, &config);
+    let srcset = |src: &str, document_dir: &Path| {
+        resolver
+            .resolve(src, document_dir)
+            .map(|p| (p.sources[0].srcset.clone(), p.fallback_uri))
+    };
+    let site = dir.join("dist");
+    let blog = dir.join("dist/blog");
+
+    // public uris below the mountpoint, keeping the query and fragment
+    assert_eq!(
+        srcset("/assets/rgb16.png?v=2#top", &blog),
+        Some((
+            "/assets/rgb16-w22.webp".to_string(),
+            "/assets/rgb16.png?v=2#top".to_string()
+        ))
+    );
+    assert_eq!(srcset("/assetsfoo/rgb16.png", &blog), None);
+    assert_eq!(srcset("/other/rgb16.png", &blog), None);
+    assert_eq!(srcset("https://example.com/assets/rgb16.png", &blog), None);
+
+    // paths relative to the document pointing into the install directory
+    assert_eq!(
+        srcset("./assets/./rgb16.png", &site),
+        Some((
+            "./assets/./rgb16-w22.webp".to_string(),
+            "./assets/./rgb16.png".to_string()
+        ))
+    );
+    assert_eq!(
+        srcset("../assets/rgb16.png#top", &blog),
+        Some((
+            "../assets/rgb16-w22.webp".to_string(),
+            "../assets/rgb16.png#top".to_string()
+        ))
+    );
+    assert_eq!(srcset("../assets/missing.png", &blog), None);
+
+    // paths pointing into the input directory are redirected to the
+    // install directory
+    assert_eq!(
+        srcset("../../assets/rgb16.png", &blog),
+        Some((
+            "../assets/rgb16-w22.webp".to_string(),
+            "../assets/rgb16.png".to_string()
+        ))
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn documents_are_rewritten_without_conversion() {
+    let dir = common::create_test_dir("rewrite-only", &["rgb16.png"]);
+    convert(&dir, &[]);
+    // a conversion would create the removed image again
+    let webp = dir.join("dist/assets/rgb16-w43.webp");
+    std::fs::remove_file(&webp).unwrap();
+
+    let html_file = dir.join("dist/index.html");
+    std::fs::write(&html_file, r#"<img src="assets/rgb16.png" alt="A">"#)
+        .unwrap();
+    let rewrite_html = dir.join("dist").display().to_string();
+    convert(&dir, &["--rewrite-only", "--rewrite-html", &rewrite_html]);
+    let html = std::fs::read_to_string(&html_file).unwrap();
+    assert!(html.starts_with("<picture>"), "{}", html);
+    assert!(
+        html.contains(r#"srcset="assets/rgb16-w22.webp""#),
+        "{}",
+        html
+    );
+    assert!(!webp.exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
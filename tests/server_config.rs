@@ -0,0 +1,118 @@
+mod common;
+
+use {
+    clap::Parser,
+    html5_picture::{core::Config, server_config::WebServer},
+    std::path::Path,
+};
+
+/// Renders the snippet for a mountpoint and an extension that contain
+/// characters having a special meaning in regular expressions.
+fn render(server: WebServer) -> String {
+    server.render(
+        Path::new("/srv/site/assets"),
+        "/static.v2/(beta)/",
+        &["gi+f".to_string(), "png".to_string()],
+        3,
+    )
+}
+
+#[test]
+fn nginx_tries_the_variants_in_order() {
+    let config = render(WebServer::Nginx);
+    assert!(config.contains(
+        "map $http_accept $html5_picture_avif {\n    default \"\";\n    \"~*image/avif\" \".avif\";\n}"
+    ));
+    assert!(config.contains(
+        "map $http_accept $html5_picture_webp {\n    default \"\";\n    \"~*image/webp\" \".webp\";\n}"
+    ));
+    assert!(config.contains(
+        r"location ~ ^/static\.v2/\(beta\)/(?<html5_picture_name>.+)\.(?<html5_picture_extension>gi\+f|png)$ {"
+    ));
+    assert!(config.contains("    root \"/srv/site/assets\";\n"));
+    assert!(config.contains(
+        "    try_files /$html5_picture_name$html5_picture_avif /$html5_picture_name$html5_picture_webp /$html5_picture_name.$html5_picture_extension =404;\n"
+    ));
+    assert!(config.contains("    add_header Vary Accept;\n"));
+}
+
+#[test]
+fn apache_rewrites_to_existing_variants() {
+    let config = render(WebServer::Apache);
+    assert!(config.contains("<Directory \"/srv/site/assets\">\n"));
+    for (extension, content_type) in
+        [("avif", "image/avif"), ("webp", "image/webp")]
+    {
+        assert!(config.contains(&format!(
+            concat!(
+                "    RewriteCond %{{HTTP_ACCEPT}} {1}\n",
+                "    RewriteCond %{{REQUEST_FILENAME}} ^(.+)\\.(gi\\+f|png)$\n",
+                "    RewriteCond %1.{0} -f\n",
+                "    RewriteRule ^(.+)\\.(gi\\+f|png)$ $1.{0} [T={1},L]\n"
+            ),
+            extension, content_type
+        )));
+    }
+    assert!(config.contains(r#"<FilesMatch "\.(gi\+f|png|avif|webp)$">"#));
+}
+
+#[test]
+fn caddy_matches_the_escaped_mountpoint() {
+    let config = render(WebServer::Caddy);
+    let pattern = r"^/static\.v2/\(beta\)/(.+)\.(gi\+f|png)$";
+    assert!(
+        config.contains(&format!("@html5_picture path_regexp {pattern}\n"))
+    );
+    for (extension, content_type) in
+        [("avif", "image/avif"), ("webp", "image/webp")]
+    {
+        assert!(config.contains(&format!(
+            concat!(
+                "@html5_picture_{0} {{\n",
+                "    header Accept *{1}*\n",
+                "    path_regexp html5_picture_{0} {2}\n",
+                "    file {{\n",
+                "        root \"/srv/site/assets\"\n",
+                "        try_files /{{re.html5_picture_{0}.1}}.{0}\n",
+                "    }}\n",
+                "}}\n",
+                // the target of the rewrite is a path, not a regex
+                "rewrite @html5_picture_{0} /static.v2/(beta)/{{re.html5_picture_{0}.1}}.{0}\n"
+            ),
+            extension, content_type, pattern
+        )));
+    }
+}
+
+#[test]
+fn svgs_are_not_negotiated() {
+    let dir = common::create_test_dir("server-config", &["rgb16.png"]);
+    std::fs::write(
+        dir.join("assets/logo.svg"),
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="32" height="16"><rect width="32" height="16" fill="red"/></svg>"#,
+    )
+    .unwrap();
+    let install_dir = dir.join("dist");
+    let config_file = dir.join("pictures.conf");
+    html5_picture::run(Config::parse_from([
+        "html5-picture".as_ref(),
+        dir.join("assets").as_os_str(),
+        "1".as_ref(),
+        "-i".as_ref(),
+        install_dir.as_os_str(),
+        "-m".as_ref(),
+        "/assets".as_ref(),
+        "--server-config".as_ref(),
+        "nginx".as_ref(),
+        "--server-config-file".as_ref(),
+        config_file.as_os_str(),
+    ]));
+
+    // the svg is rasterized for the picture tags nevertheless
+    assert!(install_dir.join("logo.webp").is_file());
+    let config = std::fs::read_to_string(&config_file).unwrap();
+    assert!(config.contains("# Generated by html5-picture for 1 images"));
+    assert!(config.contains("(?<html5_picture_extension>png)$"));
+    assert!(!config.contains("svg"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,136 @@
+use html5_picture::{
+    html5::{MediaWidth, Picture, SourceAttributes, TagFormat},
+    utils::Placeholder,
+};
+
+/// Creates a picture having a scaled and a full size source, whose `<img>`
+/// tag gets a style and a data attribute.
+fn create_picture(placeholder: Placeholder) -> Picture {
+    let source = |media_width, srcset: &str| SourceAttributes {
+        media_width: Some(media_width),
+        srcset: srcset.to_string(),
+        density: None,
+    };
+    let mut picture = Picture::from_uris(
+        vec![
+            source(MediaWidth::Max("640".to_string()), "hero-w640.webp"),
+            source(MediaWidth::Min("641".to_string()), "hero.webp"),
+        ],
+        "hero.png".to_string(),
+    );
+    picture.dominant_color = Some("#102030".to_string());
+    picture.palette_background = true;
+    picture.placeholder = Some(placeholder);
+    picture
+}
+
+/// Renders the picture below the `/assets` mountpoint.
+fn render(format: TagFormat, placeholder: Placeholder) -> String {
+    create_picture(placeholder).to_tag_string(
+        format,
+        "HeroPicture",
+        Some("/assets".to_string()),
+    )
+}
+
+/// A placeholder whose style contains a semicolon inside of `url()`.
+fn data_uri() -> Placeholder {
+    Placeholder::DataUri("data:image/webp;base64,UklG".to_string())
+}
+
+#[test]
+fn html() {
+    assert_eq!(
+        render(TagFormat::Html, data_uri()),
+        concat!(
+            r##"<picture><source media="(max-width: 640px)" srcset="/assets/hero-w640.webp">"##,
+            r##"<source media="(min-width: 641px)" srcset="/assets/hero.webp">"##,
+            r##"<img src="/assets/hero.png" alt="" data-dominant-color="#102030" "##,
+            r##"style="background-color:#102030;background-size:cover;background-image:url(data:image/webp;base64,UklG)" />"##,
+            "</picture>"
+        )
+    );
+}
+
+#[test]
+fn jsx_passes_the_style_as_object() {
+    let expected = r##"export default function HeroPicture(props) {
+  return (
+    <picture>
+      <source media="(max-width: 640px)" srcSet="/assets/hero-w640.webp" />
+      <source media="(min-width: 641px)" srcSet="/assets/hero.webp" />
+      <img src="/assets/hero.png" alt="" data-dominant-color="#102030" style={{ backgroundColor: "#102030", backgroundSize: "cover", backgroundImage: "url(data:image/webp;base64,UklG)" }} {...props} />
+    </picture>
+  );
+}
+"##;
+    assert_eq!(render(TagFormat::Jsx, data_uri()), expected);
+}
+
+#[test]
+fn tsx_types_the_properties() {
+    let expected = r##"import type { ImgHTMLAttributes } from "react";
+
+export default function HeroPicture(props: ImgHTMLAttributes<HTMLImageElement>) {
+  return (
+    <picture>
+      <source media="(max-width: 640px)" srcSet="/assets/hero-w640.webp" />
+      <source media="(min-width: 641px)" srcSet="/assets/hero.webp" />
+      <img src="/assets/hero.png" alt="" data-dominant-color="#102030" data-blurhash="L{x}%Y" style={{ backgroundColor: "#102030" }} {...props} />
+    </picture>
+  );
+}
+"##;
+    let blurhash = Placeholder::BlurHash("L{x}%Y".to_string());
+    assert_eq!(render(TagFormat::Tsx, blurhash), expected);
+}
+
+#[test]
+fn vue_binds_the_attributes_to_the_img_tag() {
+    let expected = r##"<script setup>
+defineOptions({ inheritAttrs: false });
+</script>
+
+<template>
+  <picture>
+    <source media="(max-width: 640px)" srcset="/assets/hero-w640.webp">
+    <source media="(min-width: 641px)" srcset="/assets/hero.webp">
+    <img src="/assets/hero.png" alt="" data-dominant-color="#102030" style="background-color:#102030;background-size:cover;background-image:url(data:image/webp;base64,UklG)" v-bind="$attrs">
+  </picture>
+</template>
+"##;
+    assert_eq!(render(TagFormat::Vue, data_uri()), expected);
+}
+
+#[test]
+fn svelte_escapes_curly_braces() {
+    let expected = r##"<script>
+  let props = $props();
+</script>
+
+<picture>
+  <source media="(max-width: 640px)" srcset="/assets/hero-w640.webp">
+  <source media="(min-width: 641px)" srcset="/assets/hero.webp">
+  <img src="/assets/hero.png" alt="" data-dominant-color="#102030" data-blurhash="L&#123;x&#125;%Y&quot;" style="background-color:#102030" {...props}>
+</picture>
+"##;
+    let blurhash = Placeholder::BlurHash("L{x}%Y\"".to_string());
+    assert_eq!(render(TagFormat::Svelte, blurhash), expected);
+}
+
+#[test]
+fn pass_through_images_are_rendered_as_img_tag() {
+    let mut picture =
+        Picture::from_uris(vec![], "/assets/logo.svg".to_string());
+    picture.pass_through = true;
+    let component = picture.to_tag_string(TagFormat::Jsx, "LogoPicture", None);
+    assert!(component.contains(
+        "  return (\n    <img src=\"/assets/logo.svg\" alt=\"\" {...props} />\n  );"
+    ));
+    let component =
+        picture.to_tag_string(TagFormat::Svelte, "LogoPicture", None);
+    assert!(
+        component
+            .ends_with("<img src=\"/assets/logo.svg\" alt=\"\" {...props}>\n")
+    );
+}
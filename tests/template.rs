@@ -0,0 +1,134 @@
+#![cfg(any(feature = "tera", feature = "askama"))]
+
+mod common;
+
+use {
+    clap::Parser,
+    html5_picture::{core::Config, html5::PictureRegister},
+    std::path::Path,
+};
+
+/// The `<source>` tags of the fixture served below the mountpoint.
+const SOURCES: &str = concat!(
+    r#"<picture><source media="(max-width: 22px)" srcset="/assets/rgb16-w22.webp">"#,
+    r#"<source media="(max-width: 43px)" srcset="/assets/rgb16-w43.webp">"#,
+    r#"<source media="(min-width: 44px)" srcset="/assets/rgb16.webp">"#,
+);
+
+/// Converts the fixture with a derivative and creates its register.
+fn create_register(dir: &Path) -> PictureRegister {
+    let config = Config::parse_from([
+        "html5-picture".as_ref(),
+        dir.join("assets").as_os_str(),
+        "2".as_ref(),
+        "-i".as_ref(),
+        dir.join("dist").as_os_str(),
+        "-m".as_ref(),
+        "/assets".as_ref(),
+        "--derivative".as_ref(),
+        "thumb: 1:1 @ 16".as_ref(),
+    ]);
+    html5_picture::run(config.clone());
+    PictureRegister::from(&config).unwrap()
+}
+
+#[cfg(feature = "tera")]
+#[test]
+fn tera_function_renders_pictures() {
+    use {
+        html5_picture::template::PictureFunction,
+        std::error::Error,
+        tera::{Context, Tera},
+    };
+
+    let dir = common::create_test_dir("template-tera", &["rgb16.png"]);
+    let mut tera = Tera::default();
+    tera.register_function(
+        "picture",
+        PictureFunction::new(create_register(&dir)),
+    );
+    let mut render = |template: &str| {
+        tera.render_str(template, &Context::new()).map_err(|e| {
+            // the message of the function is the source of the error
+            let mut msg = e.to_string();
+            let mut source = e.source();
+            while let Some(e) = source {
+                msg.push_str(&format!(": {}", e));
+                source = e.source();
+            }
+            msg
+        })
+    };
+
+    let html = render(
+        r#"{{ picture(path="/assets/rgb16.png", alt="Our team", sizes="100vw") }}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        html,
+        format!(
+            r#"{}<img src="/assets/rgb16.png" alt="Our team" sizes="100vw" /></picture>"#,
+            SOURCES
+        )
+    );
+    let html = render(
+        r#"{{ picture(path="rgb16.png", derivative="thumb", alt="") }}"#,
+    )
+    .unwrap();
+    assert!(html.contains(r#"srcset="/assets/rgb16-thumb-w16.webp""#));
+
+    let error = render(r#"{{ picture(path="missing.png", alt="") }}"#);
+    assert!(
+        error
+            .unwrap_err()
+            .contains(r#"Image "missing.png" not found!"#)
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "askama")]
+#[test]
+fn askama_helper_renders_pictures() {
+    use {askama::Template, html5_picture::template::PictureHelper};
+
+    #[derive(Template)]
+    #[template(
+        source = r#"<main>{{ pictures.picture_with(path, "Our team", [("class", "hero")])? }}</main>"#,
+        ext = "html"
+    )]
+    struct Index<'a> {
+        pictures: PictureHelper,
+        path: &'a str,
+    }
+
+    let dir = common::create_test_dir("template-askama", &["rgb16.png"]);
+    let pictures = PictureHelper::new(create_register(&dir));
+    let html = Index {
+        pictures: pictures.clone(),
+        path: "rgb16.png",
+    }
+    .render()
+    .unwrap();
+    assert_eq!(
+        html,
+        format!(
+            r#"<main>{}<img src="/assets/rgb16.png" alt="Our team" class="hero" /></picture></main>"#,
+            SOURCES
+        )
+    );
+    let thumb = pictures.derivative("rgb16.png", "thumb", "").unwrap();
+    assert!(thumb.0.contains(r#"srcset="/assets/rgb16-thumb-w16.webp""#));
+
+    let error = Index {
+        pictures,
+        path: "missing.png",
+    }
+    .render()
+    .unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains(r#"Image "missing.png" not found!"#)
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}